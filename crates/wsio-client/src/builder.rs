@@ -17,7 +17,15 @@ use url::Url;
 use crate::{
     WsIoClient,
     config::WsIoClientConfig,
-    core::packet::codecs::WsIoPacketCodec,
+    core::{
+        error::WsIoRemoteError,
+        event::{
+            dispatch::WsIoEventDispatchMode,
+            interceptor::WsIoEventNext,
+            registry::WsIoEventPayload,
+        },
+        packet::codecs::WsIoPacketCodec,
+    },
     runtime::WsIoClientRuntime,
     session::WsIoClientSession,
 };
@@ -47,15 +55,25 @@ impl WsIoClientBuilder {
         url.set_path("ws.io");
         Ok(Self {
             config: WsIoClientConfig {
+                event_dispatch_mode: WsIoEventDispatchMode::default(),
+                event_handler_timeout: None,
+                event_interceptors: Vec::new(),
+                heartbeat_interval: Some(Duration::from_secs(25)),
+                heartbeat_timeout: Duration::from_secs(20),
                 init_handler: None,
                 init_handler_timeout: Duration::from_secs(3),
                 init_packet_timeout: Duration::from_secs(3),
+                max_consecutive_event_errors: None,
+                on_connect_error_handler: None,
+                on_error_handler: None,
+                on_event_error_handler: None,
                 on_session_close_handler: None,
                 on_session_close_handler_timeout: Duration::from_secs(2),
                 on_session_ready_handler: None,
-                packet_codec: WsIoPacketCodec::SerdeJson,
+                packet_codecs: vec![WsIoPacketCodec::SerdeJson],
                 ready_packet_timeout: Duration::from_secs(3),
                 reconnect_delay: Duration::from_secs(1),
+                stream_credit_window: 16,
                 websocket_config: WebSocketConfig::default()
                     .max_frame_size(Some(8 * 1024 * 1024))
                     .max_message_size(Some(16 * 1024 * 1024))
@@ -80,6 +98,32 @@ impl WsIoClientBuilder {
         WsIoClient(WsIoClientRuntime::new(self.config, self.connect_url))
     }
 
+    /// Stops pinging the server, relying on the transport to notice a dead connection.
+    pub fn disable_heartbeat(mut self) -> Self {
+        self.config.heartbeat_interval = None;
+        self
+    }
+
+    pub fn event_dispatch_mode(mut self, event_dispatch_mode: WsIoEventDispatchMode) -> Self {
+        self.config.event_dispatch_mode = event_dispatch_mode;
+        self
+    }
+
+    pub fn event_handler_timeout(mut self, duration: Duration) -> Self {
+        self.config.event_handler_timeout = Some(duration);
+        self
+    }
+
+    pub fn heartbeat_interval(mut self, duration: Duration) -> Self {
+        self.config.heartbeat_interval = Some(duration);
+        self
+    }
+
+    pub fn heartbeat_timeout(mut self, duration: Duration) -> Self {
+        self.config.heartbeat_timeout = duration;
+        self
+    }
+
     pub fn init_handler_timeout(mut self, duration: Duration) -> Self {
         self.config.init_handler_timeout = duration;
         self
@@ -90,6 +134,41 @@ impl WsIoClientBuilder {
         self
     }
 
+    pub fn max_consecutive_event_errors(mut self, max_consecutive_event_errors: u32) -> Self {
+        self.config.max_consecutive_event_errors = Some(max_consecutive_event_errors);
+        self
+    }
+
+    pub fn on_connect_error<H, Fut>(mut self, handler: H) -> Self
+    where
+        H: Fn(Arc<WsIoClientSession>, WsIoRemoteError) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.config.on_connect_error_handler = Some(Arc::new(move |session, error| Box::pin(handler(session, error))));
+        self
+    }
+
+    pub fn on_error<H, Fut>(mut self, handler: H) -> Self
+    where
+        H: Fn(Arc<WsIoClientSession>, WsIoRemoteError) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.config.on_error_handler = Some(Arc::new(move |session, error| Box::pin(handler(session, error))));
+        self
+    }
+
+    pub fn on_event_error<H, Fut>(mut self, handler: H) -> Self
+    where
+        H: Fn(Arc<WsIoClientSession>, String, anyhow::Error) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.config.on_event_error_handler = Some(Box::new(move |session, event, error| {
+            Box::pin(handler(session, event, error))
+        }));
+
+        self
+    }
+
     pub fn on_session_close<H, Fut>(mut self, handler: H) -> Self
     where
         H: Fn(Arc<WsIoClientSession>) -> Fut + Send + Sync + 'static,
@@ -113,8 +192,29 @@ impl WsIoClientBuilder {
         self
     }
 
-    pub fn packet_codec(mut self, packet_codec: WsIoPacketCodec) -> Self {
-        self.config.packet_codec = packet_codec;
+    pub fn packet_codec(mut self, packet_codec: impl Into<WsIoPacketCodec>) -> Self {
+        self.config.packet_codecs = vec![packet_codec.into()];
+        self
+    }
+
+    /// Advertises `packet_codecs` to the server in order of preference, letting it pick the first one it accepts.
+    ///
+    /// Codecs sharing the name of a previous one are skipped, and an empty list keeps the current codecs.
+    pub fn packet_codecs<I: IntoIterator<Item = C>, C: Into<WsIoPacketCodec>>(mut self, packet_codecs: I) -> Self {
+        let mut unique_packet_codecs = Vec::<WsIoPacketCodec>::new();
+        for packet_codec in packet_codecs.into_iter().map(Into::into) {
+            if !unique_packet_codecs
+                .iter()
+                .any(|unique_packet_codec| unique_packet_codec.name() == packet_codec.name())
+            {
+                unique_packet_codecs.push(packet_codec);
+            }
+        }
+
+        if !unique_packet_codecs.is_empty() {
+            self.config.packet_codecs = unique_packet_codecs;
+        }
+
         self
     }
 
@@ -135,6 +235,11 @@ impl WsIoClientBuilder {
         self
     }
 
+    pub fn stream_credit_window(mut self, stream_credit_window: u32) -> Self {
+        self.config.stream_credit_window = stream_credit_window;
+        self
+    }
+
     pub fn websocket_config(mut self, websocket_config: WebSocketConfig) -> Self {
         self.config.websocket_config = websocket_config;
         self
@@ -145,6 +250,20 @@ impl WsIoClientBuilder {
         self
     }
 
+    pub fn with_event_interceptor<H, Fut>(mut self, interceptor: H) -> Self
+    where
+        H: Fn(Arc<WsIoClientSession>, String, WsIoEventPayload, WsIoEventNext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.config
+            .event_interceptors
+            .push(Arc::new(move |session, event, payload, next| {
+                Box::pin(interceptor(session, event, payload, next))
+            }));
+
+        self
+    }
+
     pub fn with_init_handler<H, Fut, D, R>(mut self, handler: H) -> WsIoClientBuilder
     where
         H: Fn(Arc<WsIoClientSession>, Option<D>) -> Fut + Send + Sync + 'static,
@@ -9,6 +9,11 @@ use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 
 use crate::{
     core::{
+        error::WsIoRemoteError,
+        event::{
+            dispatch::WsIoEventDispatchMode,
+            interceptor::WsIoEventInterceptor,
+        },
         packet::codecs::WsIoPacketCodec,
         types::{
             ArcAsyncUnaryResultHandler,
@@ -19,6 +24,20 @@ use crate::{
 };
 
 // Types
+type ErrorHandler = Arc<
+    dyn Fn(Arc<WsIoClientSession>, WsIoRemoteError) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+type EventErrorHandler = Box<
+    dyn Fn(Arc<WsIoClientSession>, String, anyhow::Error) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
 type InitHandler = Box<
     dyn for<'a> Fn(
             Arc<WsIoClientSession>,
@@ -32,6 +51,23 @@ type InitHandler = Box<
 
 // Structs
 pub(crate) struct WsIoClientConfig {
+    /// Order in which the events received from the server are handled.
+    pub(crate) event_dispatch_mode: WsIoEventDispatchMode,
+
+    /// Interceptors wrapping the handlers of every event received, the first one added being the outermost.
+    ///
+    /// They run in the job of the event, so they follow the dispatch mode and count towards the in-flight limit.
+    pub(crate) event_interceptors: Vec<WsIoEventInterceptor<WsIoClientSession>>,
+
+    /// Maximum duration allowed for an event handler to execute, unless overridden when it is registered.
+    pub(crate) event_handler_timeout: Option<Duration>,
+
+    /// Interval between ping packets sent to the server, `None` disabling the heartbeat.
+    pub(crate) heartbeat_interval: Option<Duration>,
+
+    /// Maximum duration to wait for any packet from the server after a ping before reconnecting.
+    pub(crate) heartbeat_timeout: Duration,
+
     pub(crate) init_handler: Option<InitHandler>,
 
     /// Maximum duration allowed for the init handler to execute.
@@ -40,6 +76,18 @@ pub(crate) struct WsIoClientConfig {
     /// Maximum duration to wait for the server to send the init packet.
     pub(crate) init_packet_timeout: Duration,
 
+    /// Number of consecutive event handler or payload decode failures after which the session is closed.
+    pub(crate) max_consecutive_event_errors: Option<u32>,
+
+    /// Invoked when the server rejects the handshake with an error packet.
+    pub(crate) on_connect_error_handler: Option<ErrorHandler>,
+
+    /// Invoked when the server reports an error, such as a failed event handler, on a ready session.
+    pub(crate) on_error_handler: Option<ErrorHandler>,
+
+    /// Invoked when the payload of an event fails to decode or one of its handlers fails.
+    pub(crate) on_event_error_handler: Option<EventErrorHandler>,
+
     pub(crate) on_session_close_handler: Option<BoxAsyncUnaryResultHandler<WsIoClientSession>>,
 
     /// Maximum duration allowed for the on_session_close handler to execute.
@@ -47,12 +95,16 @@ pub(crate) struct WsIoClientConfig {
 
     pub(crate) on_session_ready_handler: Option<ArcAsyncUnaryResultHandler<WsIoClientSession>>,
 
-    pub(crate) packet_codec: WsIoPacketCodec,
+    /// Packet codecs advertised to the server, in order of preference.
+    pub(crate) packet_codecs: Vec<WsIoPacketCodec>,
 
     /// Maximum duration to wait for the client to send the ready packet.
     pub(crate) ready_packet_timeout: Duration,
 
     pub(crate) reconnect_delay: Duration,
 
+    /// Number of chunks the server may send on each stream it opens before waiting for more credit.
+    pub(crate) stream_credit_window: u32,
+
     pub(crate) websocket_config: WebSocketConfig,
 }
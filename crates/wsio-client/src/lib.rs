@@ -1,6 +1,7 @@
 use std::{
     fmt::Display,
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::Result;
@@ -8,6 +9,7 @@ use serde::{
     Serialize,
     de::DeserializeOwned,
 };
+use tokio_tungstenite::tungstenite::Bytes;
 use tokio_util::sync::CancellationToken;
 use url::Url;
 pub use wsio_core as core;
@@ -19,7 +21,22 @@ pub mod session;
 
 use crate::{
     builder::WsIoClientBuilder,
-    core::traits::task::spawner::TaskSpawner,
+    core::{
+        event::{
+            pattern::WsIoEventParams,
+            subscription::WsIoSubscription,
+        },
+        outbound::priority::WsIoOutboundPriority,
+        packet::codecs::WsIoPacketCodec,
+        stream::{
+            receiver::WsIoStreamReceiver,
+            sender::WsIoStreamSender,
+        },
+        traits::{
+            event::definition::WsIoEvent,
+            task::spawner::TaskSpawner,
+        },
+    },
     runtime::WsIoClientRuntime,
     session::WsIoClientSession,
 };
@@ -55,32 +72,255 @@ impl WsIoClient {
         self.0.disconnect().await
     }
 
+    /// Number of messages dropped instead of sent, across sessions.
+    ///
+    /// Volatile emits are dropped when the outbound queue of the session is full, and messages queued while
+    /// disconnected are dropped when encoded with a packet codec that the server did not accept on reconnection.
+    #[inline]
+    pub fn dropped_message_count(&self) -> u64 {
+        self.0.dropped_message_count()
+    }
+
     pub async fn emit<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
         self.0.emit(event.as_ref(), data).await
     }
 
+    /// Emits `event`, replacing the pending message with the same `coalescing_key` if it has not been sent yet.
+    ///
+    /// Meant for state where only the latest value matters, such as the position of a player.
+    pub async fn emit_coalesced<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        coalescing_key: impl AsRef<str>,
+    ) -> Result<()> {
+        self.0
+            .emit_coalesced(event.as_ref(), data, coalescing_key.as_ref())
+            .await
+    }
+
+    pub async fn emit_event<E: WsIoEvent>(&self, event: &E) -> Result<()> {
+        self.0.emit(E::NAME, Some(event)).await
+    }
+
+    pub async fn emit_event_with_ack<E: WsIoEvent, R: DeserializeOwned + 'static>(
+        &self,
+        event: &E,
+        timeout: Duration,
+    ) -> Result<R> {
+        self.0.emit_with_ack(E::NAME, Some(event), timeout).await
+    }
+
+    /// Emits `event` with a payload that is already encoded with the packet codec of this client.
+    pub async fn emit_raw(&self, event: impl AsRef<str>, data: Option<&[u8]>) -> Result<()> {
+        self.0.emit_raw(event.as_ref(), data).await
+    }
+
+    /// Emits `event` without waiting for room in the outgoing queue, dropping it if the session is not ready or the
+    /// queue is full.
+    ///
+    /// Unlike other emits, the event is not buffered while disconnected. Meant for data that is soon superseded, such
+    /// as cursor positions or live prices.
+    #[inline]
+    pub fn emit_volatile<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
+        self.0.emit_volatile(event.as_ref(), data)
+    }
+
+    pub async fn emit_with_ack<D: Serialize, R: DeserializeOwned + 'static>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        timeout: Duration,
+    ) -> Result<R> {
+        self.0.emit_with_ack(event.as_ref(), data, timeout).await
+    }
+
+    /// Emits `event` with `attachments` sent as binary blobs next to its payload, without going through the codec.
+    pub async fn emit_with_attachments<D: Serialize, B: AsRef<[u8]>>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        attachments: &[B],
+    ) -> Result<()> {
+        self.0.emit_with_attachments(event.as_ref(), data, attachments).await
+    }
+
+    /// Emits `event` through the outbound lane of `priority`, high priority messages overtaking the queued ones.
+    pub async fn emit_with_priority<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        priority: WsIoOutboundPriority,
+    ) -> Result<()> {
+        self.0.emit_with_priority(event.as_ref(), data, priority).await
+    }
+
     #[inline]
     pub fn off(&self, event: impl AsRef<str>) {
         self.0.off(event.as_ref());
     }
 
     #[inline]
-    pub fn off_by_handler_id(&self, event: impl AsRef<str>, handler_id: u32) {
+    pub fn off_any(&self) {
+        self.0.off_any();
+    }
+
+    #[inline]
+    pub fn off_any_by_handler_id(&self, handler_id: u64) {
+        self.0.off_any_by_handler_id(handler_id);
+    }
+
+    #[inline]
+    pub fn off_by_handler_id(&self, event: impl AsRef<str>, handler_id: u64) {
         self.0.off_by_handler_id(event.as_ref(), handler_id);
     }
 
     #[inline]
-    pub fn on<H, Fut, D>(&self, event: impl AsRef<str>, handler: H) -> u32
+    pub fn off_event<E: WsIoEvent>(&self) {
+        self.0.off(E::NAME);
+    }
+
+    #[inline]
+    pub fn off_stream(&self, stream: impl AsRef<str>) {
+        self.0.off_stream(stream.as_ref());
+    }
+
+    #[inline]
+    pub fn on<H, Fut, D, R>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
     where
         H: Fn(Arc<WsIoClientSession>, Arc<D>) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<()>> + Send + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
         D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
     {
         self.0.on(event.as_ref(), handler)
     }
 
+    #[inline]
+    pub fn on_any<H, Fut>(&self, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, String, Option<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.0.on_any(handler)
+    }
+
+    #[inline]
+    pub fn on_event<E, H, Fut, R>(&self, handler: H) -> WsIoSubscription
+    where
+        E: WsIoEvent,
+        H: Fn(Arc<WsIoClientSession>, Arc<E>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.0.on(E::NAME, handler)
+    }
+
+    #[inline]
+    pub fn on_pattern<H, Fut, D, R>(&self, pattern: impl AsRef<str>, handler: H) -> Result<WsIoSubscription>
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>, WsIoEventParams) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.0.on_pattern(pattern.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn on_raw<H, Fut>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, String, Option<Bytes>, WsIoPacketCodec) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<Vec<u8>>>> + Send + 'static,
+    {
+        self.0.on_raw(event.as_ref(), handler)
+    }
+
+    /// Registers the handler of the streams named `stream` opened by the server, replacing any previous one.
+    #[inline]
+    pub fn on_stream<H, Fut, D>(&self, stream: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>, WsIoStreamReceiver<WsIoClientSession>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.0.on_stream(stream.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn on_with_attachments<H, Fut, D, R>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>, Vec<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.0.on_with_attachments(event.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn on_with_timeout<H, Fut, D, R>(
+        &self,
+        event: impl AsRef<str>,
+        timeout: Duration,
+        handler: H,
+    ) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.0.on_with_timeout(event.as_ref(), timeout, handler)
+    }
+
+    #[inline]
+    pub fn once<H, Fut, D, R>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.0.once(event.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn once_event<E, H, Fut, R>(&self, handler: H) -> WsIoSubscription
+    where
+        E: WsIoEvent,
+        H: Fn(Arc<WsIoClientSession>, Arc<E>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.0.once(E::NAME, handler)
+    }
+
+    /// Opens a stream named `stream` with the server on the current session, sending `data` along with the request.
+    pub async fn open_stream<D: Serialize>(
+        &self,
+        stream: impl AsRef<str>,
+        data: Option<&D>,
+    ) -> Result<WsIoStreamSender<WsIoClientSession>> {
+        self.0.open_stream(stream.as_ref(), data).await
+    }
+
     #[inline]
     pub fn spawn_task<F: Future<Output = Result<()>> + Send + 'static>(&self, future: F) {
         self.0.spawn_task(future);
     }
+
+    #[inline]
+    pub fn wait_for<D: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        event: impl AsRef<str>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Arc<D>>> + Send {
+        self.0.wait_for(event.as_ref(), timeout)
+    }
+
+    #[inline]
+    pub fn wait_for_event<E: WsIoEvent>(&self, timeout: Duration) -> impl Future<Output = Result<Arc<E>>> + Send {
+        self.0.wait_for(E::NAME, timeout)
+    }
 }
@@ -1,6 +1,19 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        Arc,
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+    },
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{
+    Result,
+    anyhow,
+    bail,
+};
 use arc_swap::{
     ArcSwap,
     ArcSwapOption,
@@ -23,18 +36,21 @@ use tokio::{
     sync::{
         Mutex,
         Notify,
-        mpsc::{
-            Receiver,
-            Sender,
-            channel,
-        },
     },
     task::JoinHandle,
     time::sleep,
 };
 use tokio_tungstenite::{
     connect_async_with_config,
-    tungstenite::Message,
+    tungstenite::{
+        Bytes,
+        Message,
+        client::IntoClientRequest,
+        http::{
+            HeaderValue,
+            header::SEC_WEBSOCKET_PROTOCOL,
+        },
+    },
 };
 use tokio_util::sync::CancellationToken;
 use url::Url;
@@ -42,10 +58,32 @@ use url::Url;
 use crate::{
     config::WsIoClientConfig,
     core::{
+        ack::registry::WsIoAckRegistry,
         atomic::status::AtomicStatus,
         channel_capacity_from_websocket_config,
-        event::registry::WsIoEventRegistry,
-        packet::WsIoPacket,
+        event::{
+            dispatch::WsIoEventOverflowPolicy,
+            pattern::WsIoEventParams,
+            registry::WsIoEventRegistry,
+            subscription::WsIoSubscription,
+        },
+        outbound::{
+            priority::WsIoOutboundPriority,
+            queue::{
+                WsIoOutboundMessage,
+                WsIoOutboundQueue,
+                WsIoOutboundQueueReceiver,
+            },
+        },
+        packet::{
+            WsIoPacket,
+            codecs::WsIoPacketCodec,
+        },
+        stream::{
+            receiver::WsIoStreamReceiver,
+            registry::WsIoStreamRegistry,
+            sender::WsIoStreamSender,
+        },
         traits::task::spawner::TaskSpawner,
     },
     session::WsIoClientSession,
@@ -62,18 +100,26 @@ enum RuntimeStatus {
 
 // Structs
 pub(crate) struct WsIoClientRuntime {
+    pub(crate) ack_registry: WsIoAckRegistry,
     cancel_token: ArcSwap<CancellationToken>,
     pub(crate) config: WsIoClientConfig,
     connect_url: Url,
     connection_loop_task: Mutex<Option<JoinHandle<()>>>,
+    pub(crate) dropped_message_count: AtomicU64,
     pub(crate) event_message_flush_notify: Notify,
     event_message_flush_task: Mutex<Option<JoinHandle<()>>>,
-    event_message_send_rx: Mutex<Receiver<Arc<Message>>>,
-    event_message_send_tx: Sender<Arc<Message>>,
+    event_message_send_queue: WsIoOutboundQueue,
+    event_message_send_rx: Mutex<WsIoOutboundQueueReceiver>,
     pub(crate) event_registry: WsIoEventRegistry<WsIoClientSession, WsIoClientRuntime>,
     operate_lock: Mutex<()>,
+
+    /// Packet codec negotiated by the latest session, or the preferred one before the first, encoding the emitted
+    /// messages.
+    packet_codec: ArcSwap<WsIoPacketCodec>,
+
     session: ArcSwapOption<WsIoClientSession>,
     status: AtomicStatus<RuntimeStatus>,
+    pub(crate) stream_registry: WsIoStreamRegistry<WsIoClientSession>,
     wake_reconnect_wait_notify: Notify,
 }
 
@@ -87,30 +133,78 @@ impl TaskSpawner for WsIoClientRuntime {
 impl WsIoClientRuntime {
     pub(crate) fn new(config: WsIoClientConfig, connect_url: Url) -> Arc<Self> {
         let channel_capacity = channel_capacity_from_websocket_config(&config.websocket_config);
-        let (event_message_send_tx, event_message_send_rx) = channel(channel_capacity);
+        let (event_message_send_queue, event_message_send_rx) = WsIoOutboundQueue::new(channel_capacity);
+        let event_registry = WsIoEventRegistry::new(
+            config.event_dispatch_mode,
+            config.event_handler_timeout,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            config.event_interceptors.clone(),
+        );
+        let packet_codec = config.packet_codecs[0].clone();
+        let stream_registry = WsIoStreamRegistry::new(config.stream_credit_window);
         Arc::new(Self {
+            ack_registry: WsIoAckRegistry::new(),
             cancel_token: ArcSwap::new(Arc::new(CancellationToken::new())),
             config,
             connect_url,
             connection_loop_task: Mutex::new(None),
+            dropped_message_count: AtomicU64::new(0),
             event_message_flush_notify: Notify::new(),
             event_message_flush_task: Mutex::new(None),
+            event_message_send_queue,
             event_message_send_rx: Mutex::new(event_message_send_rx),
-            event_message_send_tx,
-            event_registry: WsIoEventRegistry::new(),
+            event_registry,
             operate_lock: Mutex::new(()),
+            packet_codec: ArcSwap::new(Arc::new(packet_codec)),
             session: ArcSwapOption::new(None),
             status: AtomicStatus::new(RuntimeStatus::Stopped),
+            stream_registry,
             wake_reconnect_wait_notify: Notify::new(),
         })
     }
 
     // Private methods
     async fn run_connection(self: &Arc<Self>) -> Result<()> {
-        let (ws_stream, _) =
-            connect_async_with_config(self.connect_url.as_str(), Some(self.config.websocket_config), false).await?;
-
-        let (session, mut message_rx) = WsIoClientSession::new(self.clone());
+        // Advertise packet codecs as sub-protocols so the server can negotiate one, starting with the codec in use so
+        // that messages queued while disconnected stay decodable whenever the server still accepts it
+        let packet_codec = self.packet_codec();
+        let sub_protocols = [packet_codec.sub_protocol()]
+            .into_iter()
+            .chain(
+                self.config
+                    .packet_codecs
+                    .iter()
+                    .filter(|preferred_packet_codec| preferred_packet_codec.name() != packet_codec.name())
+                    .map(WsIoPacketCodec::sub_protocol),
+            )
+            .collect::<Vec<_>>();
+
+        let mut request = self.connect_url.as_str().into_client_request()?;
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_str(&sub_protocols.join(", "))?,
+        );
+
+        let (ws_stream, response) =
+            connect_async_with_config(request, Some(self.config.websocket_config), false).await?;
+
+        let sub_protocol = response
+            .headers()
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|value| value.to_str().ok());
+
+        let Some(packet_codec) = self
+            .config
+            .packet_codecs
+            .iter()
+            .find(|packet_codec| sub_protocol == Some(packet_codec.sub_protocol().as_str()))
+        else {
+            bail!("Server selected unknown sub-protocol {sub_protocol:?}");
+        };
+
+        self.packet_codec.store(Arc::new(packet_codec.clone()));
+        let (session, mut message_rx) = WsIoClientSession::new(self.clone(), packet_codec.clone());
         session.init().await;
 
         let (mut ws_stream_writer, mut ws_stream_reader) = ws_stream.split();
@@ -118,9 +212,9 @@ impl WsIoClientRuntime {
         let mut read_ws_stream_task = spawn(async move {
             while let Some(message) = ws_stream_reader.next().await {
                 if match message {
-                    Ok(Message::Binary(bytes)) => session_clone.handle_incoming_packet(&bytes).await,
+                    Ok(Message::Binary(bytes)) => session_clone.handle_incoming_packet(bytes, true).await,
                     Ok(Message::Close(_)) => break,
-                    Ok(Message::Text(text)) => session_clone.handle_incoming_packet(text.as_bytes()).await,
+                    Ok(Message::Text(text)) => session_clone.handle_incoming_packet(text.into(), false).await,
                     Err(_) => break,
                     _ => Ok(()),
                 }
@@ -133,7 +227,7 @@ impl WsIoClientRuntime {
 
         let mut write_ws_stream_task = spawn(async move {
             while let Some(message) = message_rx.recv().await {
-                let message = (*message).clone();
+                let message = (**message.message()).clone();
                 let is_close = matches!(message, Message::Close(_));
                 if ws_stream_writer.send(message).await.is_err() {
                     break;
@@ -154,6 +248,11 @@ impl WsIoClientRuntime {
             _ = &mut write_ws_stream_task => {
                 read_ws_stream_task.abort();
             },
+            _ = session.run_heartbeat() => {
+                // Server is unresponsive, drop the socket so the connection loop reconnects
+                read_ws_stream_task.abort();
+                write_ws_stream_task.abort();
+            },
         }
 
         self.session.store(None);
@@ -200,10 +299,19 @@ impl WsIoClientRuntime {
             let mut event_message_send_rx = runtime.event_message_send_rx.lock().await;
             while let Some(message) = event_message_send_rx.recv().await {
                 loop {
-                    if let Some(session) = runtime.session.load().as_ref()
-                        && session.emit_event_message(message.clone()).await.is_ok()
-                    {
-                        break;
+                    if let Some(session) = runtime.session.load().as_ref() {
+                        // Messages encoded with a codec the server did not accept again could never be decoded
+                        if message
+                            .packet_codec()
+                            .is_some_and(|packet_codec| packet_codec.name() != session.packet_codec().name())
+                        {
+                            runtime.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+                            break;
+                        }
+
+                        if session.emit_event_message(message.clone()).await.is_ok() {
+                            break;
+                        }
                     }
 
                     runtime.event_message_flush_notify.notified().await;
@@ -231,9 +339,11 @@ impl WsIoClientRuntime {
             event_message_flush_task.abort();
         }
 
-        // Drop all pending event messages in the channel
-        let mut event_message_send_rx = self.event_message_send_rx.lock().await;
-        while event_message_send_rx.try_recv().is_ok() {}
+        // Drop all pending event messages in the queue
+        self.event_message_send_rx.lock().await.clear();
+
+        // Drop all pending acks so their waiters fail immediately
+        self.ack_registry.clear();
 
         // Wake reconnect loop to break out of sleep early
         self.wake_reconnect_wait_notify.notify_waiters();
@@ -251,18 +361,161 @@ impl WsIoClientRuntime {
         self.status.store(RuntimeStatus::Stopped);
     }
 
+    #[inline]
+    pub(crate) fn dropped_message_count(&self) -> u64 {
+        self.dropped_message_count.load(Ordering::Relaxed)
+    }
+
     pub(crate) async fn emit<D: Serialize>(&self, event: &str, data: Option<&D>) -> Result<()> {
         self.status.ensure(RuntimeStatus::Running, |status| {
             format!("Cannot emit in invalid status: {status:?}")
         })?;
 
-        self.event_message_send_tx
-            .send(
-                self.encode_packet_to_message(&WsIoPacket::new_event(
-                    event,
-                    data.map(|data| self.config.packet_codec.encode_data(data))
-                        .transpose()?,
-                ))?,
+        let packet_codec = self.packet_codec();
+        let packet_data = data.map(|data| packet_codec.encode_data(data)).transpose()?;
+        self.event_message_send_queue
+            .push(
+                WsIoOutboundMessage::from(
+                    self.encode_packet_to_message(&packet_codec, &WsIoPacket::new_event(event, packet_data))?,
+                )
+                .with_packet_codec(packet_codec),
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn emit_coalesced<D: Serialize>(
+        &self,
+        event: &str,
+        data: Option<&D>,
+        coalescing_key: &str,
+    ) -> Result<()> {
+        self.status.ensure(RuntimeStatus::Running, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        let packet_codec = self.packet_codec();
+        let packet_data = data.map(|data| packet_codec.encode_data(data)).transpose()?;
+        self.event_message_send_queue
+            .push(
+                WsIoOutboundMessage::coalesced(
+                    self.encode_packet_to_message(&packet_codec, &WsIoPacket::new_event(event, packet_data))?,
+                    coalescing_key.into(),
+                )
+                .with_packet_codec(packet_codec),
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn emit_raw(&self, event: &str, data: Option<&[u8]>) -> Result<()> {
+        self.status.ensure(RuntimeStatus::Running, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        let packet_codec = self.packet_codec();
+        self.event_message_send_queue
+            .push(
+                WsIoOutboundMessage::from(
+                    self.encode_packet_to_message(&packet_codec, &WsIoPacket::new_raw_event(event, data))?,
+                )
+                .with_packet_codec(packet_codec),
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn emit_volatile<D: Serialize>(&self, event: &str, data: Option<&D>) -> Result<()> {
+        // Volatile events skip the buffer kept while disconnected, they are only worth sending to the current session
+        let Some(session) = self.session.load_full() else {
+            return Ok(());
+        };
+
+        let packet_codec = session.packet_codec();
+        let packet_data = data.map(|data| packet_codec.encode_data(data)).transpose()?;
+        session.emit_volatile_event_message(
+            self.encode_packet_to_message(packet_codec, &WsIoPacket::new_event(event, packet_data))?,
+        );
+
+        Ok(())
+    }
+
+    pub(crate) async fn emit_with_ack<D: Serialize, R: DeserializeOwned + 'static>(
+        &self,
+        event: &str,
+        data: Option<&D>,
+        timeout: Duration,
+    ) -> Result<R> {
+        self.status.ensure(RuntimeStatus::Running, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        let packet_codec = self.packet_codec();
+        let packet_data = data.map(|data| packet_codec.encode_data(data)).transpose()?;
+        let pending_ack = self.ack_registry.register();
+        let packet = WsIoPacket::new_event_with_ack(event, pending_ack.ack_id(), packet_data);
+        self.event_message_send_queue
+            .push(
+                WsIoOutboundMessage::from(self.encode_packet_to_message(&packet_codec, &packet)?)
+                    .with_packet_codec(packet_codec.clone()),
+                None,
+            )
+            .await?;
+
+        pending_ack.wait(timeout, &packet_codec).await
+    }
+
+    pub(crate) async fn emit_with_attachments<D: Serialize, B: AsRef<[u8]>>(
+        &self,
+        event: &str,
+        data: Option<&D>,
+        attachments: &[B],
+    ) -> Result<()> {
+        self.status.ensure(RuntimeStatus::Running, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        let packet_codec = self.packet_codec();
+        let packet_data = data.map(|data| packet_codec.encode_data(data)).transpose()?;
+        let packet =
+            WsIoPacket::new_event_with_attachments(event, packet_data, attachments.iter().map(AsRef::as_ref).collect());
+        self.event_message_send_queue
+            .push(
+                WsIoOutboundMessage::from(self.encode_packet_to_message(&packet_codec, &packet)?)
+                    .with_packet_codec(packet_codec),
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn emit_with_priority<D: Serialize>(
+        &self,
+        event: &str,
+        data: Option<&D>,
+        priority: WsIoOutboundPriority,
+    ) -> Result<()> {
+        self.status.ensure(RuntimeStatus::Running, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        let packet_codec = self.packet_codec();
+        let packet_data = data.map(|data| packet_codec.encode_data(data)).transpose()?;
+        self.event_message_send_queue
+            .push(
+                WsIoOutboundMessage::from(
+                    self.encode_packet_to_message(&packet_codec, &WsIoPacket::new_event(event, packet_data))?,
+                )
+                .with_packet_codec(packet_codec)
+                .with_priority(priority),
+                None,
             )
             .await?;
 
@@ -270,10 +523,14 @@ impl WsIoClientRuntime {
     }
 
     #[inline]
-    pub(crate) fn encode_packet_to_message(&self, packet: &WsIoPacket) -> Result<Arc<Message>> {
-        let bytes = self.config.packet_codec.encode(packet)?;
-        Ok(Arc::new(match self.config.packet_codec.is_text() {
-            true => Message::Text(unsafe { String::from_utf8_unchecked(bytes).into() }),
+    pub(crate) fn encode_packet_to_message(
+        &self,
+        packet_codec: &WsIoPacketCodec,
+        packet: &WsIoPacket,
+    ) -> Result<Arc<Message>> {
+        let bytes = packet_codec.encode(packet)?;
+        Ok(Arc::new(match packet_codec.is_text_frame(packet) {
+            true => Message::Text(packet_codec.into_text(bytes)?.into()),
             false => Message::Binary(bytes.into()),
         }))
     }
@@ -284,17 +541,139 @@ impl WsIoClientRuntime {
     }
 
     #[inline]
-    pub(crate) fn off_by_handler_id(&self, event: &str, handler_id: u32) {
+    pub(crate) fn off_any(&self) {
+        self.event_registry.off_any();
+    }
+
+    #[inline]
+    pub(crate) fn off_any_by_handler_id(&self, handler_id: u64) {
+        self.event_registry.off_any_by_handler_id(handler_id);
+    }
+
+    #[inline]
+    pub(crate) fn off_by_handler_id(&self, event: &str, handler_id: u64) {
         self.event_registry.off_by_handler_id(event, handler_id);
     }
 
     #[inline]
-    pub(crate) fn on<H, Fut, D>(&self, event: &str, handler: H) -> u32
+    pub(crate) fn off_stream(&self, stream: &str) {
+        self.stream_registry.off(stream);
+    }
+
+    #[inline]
+    pub(crate) fn on<H, Fut, D, R>(&self, event: &str, handler: H) -> WsIoSubscription
     where
         H: Fn(Arc<WsIoClientSession>, Arc<D>) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<()>> + Send + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
         D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
     {
         self.event_registry.on(event, handler)
     }
+
+    #[inline]
+    pub(crate) fn on_any<H, Fut>(&self, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, String, Option<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.event_registry.on_any(handler)
+    }
+
+    #[inline]
+    pub(crate) fn on_pattern<H, Fut, D, R>(&self, pattern: &str, handler: H) -> Result<WsIoSubscription>
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>, WsIoEventParams) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on_pattern(pattern, handler)
+    }
+
+    #[inline]
+    pub(crate) fn on_raw<H, Fut>(&self, event: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, String, Option<Bytes>, WsIoPacketCodec) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<Vec<u8>>>> + Send + 'static,
+    {
+        self.event_registry.on_raw(event, handler)
+    }
+
+    #[inline]
+    pub(crate) fn on_stream<H, Fut, D>(&self, stream: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>, WsIoStreamReceiver<WsIoClientSession>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.stream_registry.on(stream, handler)
+    }
+
+    #[inline]
+    pub(crate) fn on_with_attachments<H, Fut, D, R>(&self, event: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>, Vec<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on_with_attachments(event, handler)
+    }
+
+    #[inline]
+    pub(crate) fn on_with_timeout<H, Fut, D, R>(&self, event: &str, timeout: Duration, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on_with_timeout(event, timeout, handler)
+    }
+
+    #[inline]
+    pub(crate) fn once<H, Fut, D, R>(&self, event: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoClientSession>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.once(event, handler)
+    }
+
+    pub(crate) async fn open_stream<D: Serialize>(
+        &self,
+        stream: &str,
+        data: Option<&D>,
+    ) -> Result<WsIoStreamSender<WsIoClientSession>> {
+        self.status.ensure(RuntimeStatus::Running, |status| {
+            format!("Cannot open stream in invalid status: {status:?}")
+        })?;
+
+        // Streams belong to the session they were opened on and fail once it closes
+        let session = self
+            .session
+            .load_full()
+            .ok_or_else(|| anyhow!("Cannot open stream without an active session"))?;
+
+        let packet_data = data.map(|data| session.packet_codec().encode_data(data)).transpose()?;
+
+        self.stream_registry.open(&session, stream, packet_data).await
+    }
+
+    #[inline]
+    pub(crate) fn packet_codec(&self) -> WsIoPacketCodec {
+        (**self.packet_codec.load()).clone()
+    }
+
+    #[inline]
+    pub(crate) fn wait_for<D: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        event: &str,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Arc<D>>> + Send + use<D> {
+        self.event_registry.wait_for(event, timeout)
+    }
 }
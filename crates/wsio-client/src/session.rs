@@ -1,4 +1,14 @@
-use std::sync::Arc;
+use std::{
+    future::pending,
+    sync::{
+        Arc,
+        atomic::{
+            AtomicU16,
+            AtomicU32,
+            Ordering,
+        },
+    },
+};
 
 use anyhow::{
     Result,
@@ -13,11 +23,7 @@ use tokio::{
     spawn,
     sync::{
         Mutex,
-        mpsc::{
-            Receiver,
-            Sender,
-            channel,
-        },
+        Notify,
     },
     task::JoinHandle,
     time::{
@@ -25,7 +31,14 @@ use tokio::{
         timeout,
     },
 };
-use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::{
+    Bytes,
+    Message,
+    protocol::{
+        CloseFrame,
+        frame::coding::CloseCode,
+    },
+};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
@@ -33,12 +46,39 @@ use crate::{
     core::{
         atomic::status::AtomicStatus,
         channel_capacity_from_websocket_config,
+        error::{
+            WsIoRemoteError,
+            encode_error_packet,
+        },
+        event::registry::WsIoEventPayload,
+        outbound::{
+            priority::WsIoOutboundPriority,
+            queue::{
+                WsIoOutboundMessage,
+                WsIoOutboundQueue,
+                WsIoOutboundQueueReceiver,
+            },
+        },
         packet::{
             WsIoPacket,
             WsIoPacketType,
+            codecs::WsIoPacketCodec,
+        },
+        protocol::version::negotiate_protocol_version,
+        stream::registry::WsIoStreamRegistry,
+        traits::{
+            ack::sender::AckSender,
+            error::{
+                reporter::EventErrorReporter,
+                sender::ErrorSender,
+            },
+            stream::sender::StreamPacketSender,
+            task::spawner::TaskSpawner,
+        },
+        utils::{
+            heartbeat::run_heartbeat,
+            task::abort_locked_task,
         },
-        traits::task::spawner::TaskSpawner,
-        utils::task::abort_locked_task,
     },
     runtime::WsIoClientRuntime,
 };
@@ -60,13 +100,79 @@ enum SessionStatus {
 // Structs
 pub struct WsIoClientSession {
     cancel_token: ArcSwap<CancellationToken>,
+    consecutive_event_errors: AtomicU32,
+    heartbeat_notify: Notify,
     init_timeout_task: Mutex<Option<JoinHandle<()>>>,
-    message_tx: Sender<Arc<Message>>,
+    message_queue: WsIoOutboundQueue,
+    packet_codec: WsIoPacketCodec,
+    protocol_version: AtomicU16,
     ready_timeout_task: Mutex<Option<JoinHandle<()>>>,
     runtime: Arc<WsIoClientRuntime>,
     status: AtomicStatus<SessionStatus>,
 }
 
+impl AckSender for WsIoClientSession {
+    #[inline]
+    async fn send_ack(&self, ack_id: u32, data: Option<Vec<u8>>) -> Result<()> {
+        self.send_packet(&WsIoPacket::new_ack(ack_id, data)).await
+    }
+}
+
+impl ErrorSender for WsIoClientSession {
+    #[inline]
+    async fn send_error(&self, error: &anyhow::Error, ack_id: Option<u32>) -> Result<()> {
+        self.send_packet(&WsIoPacket {
+            ack_id,
+            ..encode_error_packet(error, &self.packet_codec)?
+        })
+        .await
+    }
+}
+
+impl EventErrorReporter for WsIoClientSession {
+    #[inline]
+    fn clear_event_errors(&self) {
+        self.consecutive_event_errors.store(0, Ordering::Relaxed);
+    }
+
+    async fn report_event_error(self: &Arc<Self>, event: &str, error: anyhow::Error) {
+        let consecutive_event_errors = self.consecutive_event_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(on_event_error_handler) = &self.runtime.config.on_event_error_handler {
+            let _ = on_event_error_handler(self.clone(), event.into(), error).await;
+        }
+
+        if self
+            .runtime
+            .config
+            .max_consecutive_event_errors
+            .is_some_and(|max_consecutive_event_errors| consecutive_event_errors >= max_consecutive_event_errors)
+        {
+            self.close();
+        }
+    }
+}
+
+impl StreamPacketSender for WsIoClientSession {
+    #[inline]
+    fn packet_codec(&self) -> &WsIoPacketCodec {
+        &self.packet_codec
+    }
+
+    #[inline]
+    async fn send_stream_packet(&self, packet: &WsIoPacket<'_>) -> Result<()> {
+        self.emit_event_message(
+            WsIoOutboundMessage::from(self.runtime.encode_packet_to_message(&self.packet_codec, packet)?)
+                .with_priority(WsIoOutboundPriority::for_packet_type(packet.r#type)),
+        )
+        .await
+    }
+
+    #[inline]
+    fn stream_registry(&self) -> &WsIoStreamRegistry<Self> {
+        &self.runtime.stream_registry
+    }
+}
+
 impl TaskSpawner for WsIoClientSession {
     #[inline]
     fn cancel_token(&self) -> Arc<CancellationToken> {
@@ -76,14 +182,21 @@ impl TaskSpawner for WsIoClientSession {
 
 impl WsIoClientSession {
     #[inline]
-    pub(crate) fn new(runtime: Arc<WsIoClientRuntime>) -> (Arc<Self>, Receiver<Arc<Message>>) {
+    pub(crate) fn new(
+        runtime: Arc<WsIoClientRuntime>,
+        packet_codec: WsIoPacketCodec,
+    ) -> (Arc<Self>, WsIoOutboundQueueReceiver) {
         let channel_capacity = channel_capacity_from_websocket_config(&runtime.config.websocket_config);
-        let (message_tx, message_rx) = channel(channel_capacity);
+        let (message_queue, message_rx) = WsIoOutboundQueue::new(channel_capacity);
         (
             Arc::new(Self {
                 cancel_token: ArcSwap::new(Arc::new(CancellationToken::new())),
+                consecutive_event_errors: AtomicU32::new(0),
+                heartbeat_notify: Notify::new(),
                 init_timeout_task: Mutex::new(None),
-                message_tx,
+                message_queue,
+                packet_codec,
+                protocol_version: AtomicU16::new(0),
                 ready_timeout_task: Mutex::new(None),
                 runtime,
                 status: AtomicStatus::new(SessionStatus::Created),
@@ -93,6 +206,18 @@ impl WsIoClientSession {
     }
 
     // Private methods
+    #[inline]
+    fn close_with_frame(&self, close_frame: Option<CloseFrame>) {
+        // Skip if session is already Closing or Closed, otherwise set state to Closing
+        match self.status.get() {
+            SessionStatus::Closed | SessionStatus::Closing => return,
+            _ => self.status.store(SessionStatus::Closing),
+        }
+
+        // Send websocket close frame to initiate graceful shutdown
+        let _ = self.message_queue.try_push(Arc::new(Message::Close(close_frame)));
+    }
+
     #[inline]
     fn handle_disconnect_packet(&self) -> Result<()> {
         let runtime = self.runtime.clone();
@@ -100,20 +225,54 @@ impl WsIoClientSession {
         Ok(())
     }
 
-    #[inline]
-    fn handle_event_packet(self: &Arc<Self>, event: &str, packet_data: Option<Vec<u8>>) -> Result<()> {
-        self.runtime.event_registry.dispatch_event_packet(
-            self.clone(),
-            event,
-            &self.runtime.config.packet_codec,
-            packet_data,
-            &self.runtime,
-        );
+    fn handle_error_packet(self: &Arc<Self>, packet: WsIoPacket) -> Result<()> {
+        let ack_id = packet.ack_id;
+        let error = WsIoRemoteError::from_packet(packet, &self.packet_codec);
+
+        // Deliver errors answering an ack to the waiting emitter
+        if let Some(ack_id) = ack_id {
+            self.runtime.ack_registry.reject(ack_id, error);
+            return Ok(());
+        }
+
+        if self.status.is(SessionStatus::Ready) {
+            // Invoke on_error handler if configured
+            if let Some(on_error_handler) = self.runtime.config.on_error_handler.clone() {
+                self.spawn_task(on_error_handler(self.clone(), error));
+            }
+        } else if let Some(on_connect_error_handler) = self.runtime.config.on_connect_error_handler.clone() {
+            // Run handler detached from the session since the server closes it right after rejecting the handshake
+            spawn(on_connect_error_handler(self.clone(), error));
+        }
 
         Ok(())
     }
 
-    async fn handle_init_packet(self: &Arc<Self>, packet_data: Option<&[u8]>) -> Result<()> {
+    #[inline]
+    async fn handle_event_packet(
+        self: &Arc<Self>,
+        event: &str,
+        packet_data: Option<Bytes>,
+        attachments: Vec<Bytes>,
+        ack_id: Option<u32>,
+    ) -> Result<()> {
+        self.runtime
+            .event_registry
+            .dispatch_event_packet(
+                self.clone(),
+                event,
+                &self.packet_codec,
+                WsIoEventPayload {
+                    attachments,
+                    data: packet_data,
+                },
+                ack_id,
+                &self.runtime,
+            )
+            .await
+    }
+
+    async fn handle_init_packet(self: &Arc<Self>, packet_data: Option<&[u8]>, version: Option<u16>) -> Result<()> {
         // Verify current state; only valid from AwaitingInit → Initiating
         let status = self.status.get();
         match status {
@@ -124,11 +283,26 @@ impl WsIoClientSession {
         // Abort init-timeout task if still active
         abort_locked_task(&self.init_timeout_task).await;
 
+        // Negotiate protocol version, closing with the reason if the server is incompatible
+        let protocol_version = match negotiate_protocol_version(version) {
+            Ok(protocol_version) => protocol_version,
+            Err(err) => {
+                self.close_with_frame(Some(CloseFrame {
+                    code: CloseCode::Protocol,
+                    reason: err.to_string().into(),
+                }));
+
+                return Ok(());
+            }
+        };
+
+        self.protocol_version.store(protocol_version, Ordering::Relaxed);
+
         // Invoke init_handler with timeout protection if configured
         let response_data = if let Some(init_handler) = &self.runtime.config.init_handler {
             timeout(
                 self.runtime.config.init_handler_timeout,
-                init_handler(self.clone(), packet_data, &self.runtime.config.packet_codec),
+                init_handler(self.clone(), packet_data, &self.packet_codec),
             )
             .await??
         } else {
@@ -149,7 +323,8 @@ impl WsIoClientSession {
         }));
 
         // Send init packet
-        self.send_packet(&WsIoPacket::new_init(response_data)).await
+        self.send_packet(&WsIoPacket::new_init(protocol_version, response_data))
+            .await
     }
 
     async fn handle_ready_packet(self: &Arc<Self>) -> Result<()> {
@@ -175,12 +350,17 @@ impl WsIoClientSession {
         Ok(())
     }
 
-    async fn send_message(&self, message: Arc<Message>) -> Result<()> {
-        Ok(self.message_tx.send(message).await?)
+    async fn send_message(&self, message: impl Into<WsIoOutboundMessage>) -> Result<()> {
+        self.message_queue.push(message, None).await?;
+        Ok(())
     }
 
-    async fn send_packet(&self, packet: &WsIoPacket) -> Result<()> {
-        self.send_message(self.runtime.encode_packet_to_message(packet)?).await
+    async fn send_packet(&self, packet: &WsIoPacket<'_>) -> Result<()> {
+        self.send_message(
+            WsIoOutboundMessage::from(self.runtime.encode_packet_to_message(&self.packet_codec, packet)?)
+                .with_priority(WsIoOutboundPriority::for_packet_type(packet.r#type)),
+        )
+        .await
     }
 
     // Protected methods
@@ -195,6 +375,10 @@ impl WsIoClientSession {
         // Cancel all ongoing operations via cancel token
         self.cancel_token.load().cancel();
 
+        // Fail all pending acks and open streams so their senders and receivers stop waiting
+        self.runtime.ack_registry.clear();
+        self.runtime.stream_registry.clear();
+
         // Invoke on_session_close handler with timeout protection if configured
         if let Some(on_session_close_handler) = &self.runtime.config.on_session_close_handler {
             let _ = timeout(
@@ -210,17 +394,10 @@ impl WsIoClientSession {
 
     #[inline]
     pub(crate) fn close(&self) {
-        // Skip if session is already Closing or Closed, otherwise set state to Closing
-        match self.status.get() {
-            SessionStatus::Closed | SessionStatus::Closing => return,
-            _ => self.status.store(SessionStatus::Closing),
-        }
-
-        // Send websocket close frame to initiate graceful shutdown
-        let _ = self.message_tx.try_send(Arc::new(Message::Close(None)));
+        self.close_with_frame(None);
     }
 
-    pub(crate) async fn emit_event_message(&self, message: Arc<Message>) -> Result<()> {
+    pub(crate) async fn emit_event_message(&self, message: impl Into<WsIoOutboundMessage>) -> Result<()> {
         self.status.ensure(SessionStatus::Ready, |status| {
             format!("Cannot emit event message in invalid status: {status:?}")
         })?;
@@ -228,20 +405,52 @@ impl WsIoClientSession {
         self.send_message(message).await
     }
 
-    pub(crate) async fn handle_incoming_packet(self: &Arc<Self>, bytes: &[u8]) -> Result<()> {
-        // TODO: lazy load
-        let packet = self.runtime.config.packet_codec.decode(bytes)?;
+    /// Queues `message` without waiting, dropping it if the session is not ready or its queue is full.
+    #[inline]
+    pub(crate) fn emit_volatile_event_message(&self, message: Arc<Message>) {
+        if self.status.is(SessionStatus::Ready) && matches!(self.message_queue.try_push(message), Ok(false)) {
+            self.runtime.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) async fn handle_incoming_packet(self: &Arc<Self>, frame: Bytes, is_binary: bool) -> Result<()> {
+        // Any packet proves the server is alive
+        self.heartbeat_notify.notify_one();
+
+        // Payload stays borrowed from the frame until a registered handler needs it
+        let mut packet = self.packet_codec.decode_frame(&frame, is_binary)?;
         match packet.r#type {
+            WsIoPacketType::Ack => {
+                if let Some(ack_id) = packet.ack_id {
+                    self.runtime
+                        .ack_registry
+                        .resolve(ack_id, packet.take_frame_data(&frame));
+                    Ok(())
+                } else {
+                    bail!("Ack packet missing ack id");
+                }
+            }
             WsIoPacketType::Disconnect => self.handle_disconnect_packet(),
+            WsIoPacketType::Error => self.handle_error_packet(packet),
             WsIoPacketType::Event => {
+                let packet_data = packet.take_frame_data(&frame);
+                let attachments = packet.take_frame_attachments(&frame);
                 if let Some(event) = packet.key.as_deref() {
-                    self.handle_event_packet(event, packet.data)
+                    self.handle_event_packet(event, packet_data, attachments, packet.ack_id)
+                        .await
                 } else {
                     bail!("Event packet missing key");
                 }
             }
-            WsIoPacketType::Init => self.handle_init_packet(packet.data.as_deref()).await,
+            WsIoPacketType::Init => self.handle_init_packet(packet.data.as_deref(), packet.version).await,
+            WsIoPacketType::Ping => self.send_packet(&WsIoPacket::new_pong()).await,
+            WsIoPacketType::Pong | WsIoPacketType::Unknown(_) => Ok(()),
             WsIoPacketType::Ready => self.handle_ready_packet().await,
+            WsIoPacketType::StreamCancel
+            | WsIoPacketType::StreamCredit
+            | WsIoPacketType::StreamData
+            | WsIoPacketType::StreamEnd
+            | WsIoPacketType::StreamOpen => self.runtime.stream_registry.handle_packet(self, packet, &frame).await,
         }
     }
 
@@ -256,8 +465,47 @@ impl WsIoClientSession {
         }));
     }
 
+    /// Sends pings periodically and returns once the server stays silent for longer than the heartbeat timeout, never
+    /// returning if the heartbeat is disabled.
+    pub(crate) async fn run_heartbeat(&self) {
+        let Some(heartbeat_interval) = self.runtime.config.heartbeat_interval else {
+            return pending().await;
+        };
+
+        run_heartbeat(
+            heartbeat_interval,
+            self.runtime.config.heartbeat_timeout,
+            &self.heartbeat_notify,
+            &self.runtime.event_registry,
+            || {
+                // Skip the ping if the outgoing queue is full, the pending traffic will keep the server busy anyway
+                if let Ok(message) = self
+                    .runtime
+                    .encode_packet_to_message(&self.packet_codec, &WsIoPacket::new_ping())
+                {
+                    let _ = self
+                        .message_queue
+                        .try_push(WsIoOutboundMessage::from(message).with_priority(WsIoOutboundPriority::High));
+                }
+            },
+        )
+        .await
+    }
+
     // Public methods
     pub fn client(&self) -> WsIoClient {
         WsIoClient(self.runtime.clone())
     }
+
+    /// Packet codec negotiated with the server during the handshake.
+    #[inline]
+    pub fn packet_codec(&self) -> &WsIoPacketCodec {
+        &self.packet_codec
+    }
+
+    /// Protocol version negotiated with the server, `0` until its init packet is received.
+    #[inline]
+    pub fn protocol_version(&self) -> u16 {
+        self.protocol_version.load(Ordering::Relaxed)
+    }
 }
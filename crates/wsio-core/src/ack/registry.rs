@@ -0,0 +1,184 @@
+use std::{
+    sync::atomic::{
+        AtomicU32,
+        Ordering,
+    },
+    time::Duration,
+};
+
+use anyhow::{
+    Result,
+    anyhow,
+    bail,
+};
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use tokio::{
+    sync::oneshot::{
+        Receiver,
+        Sender,
+        channel,
+    },
+    time::timeout,
+};
+
+use crate::{
+    error::WsIoRemoteError,
+    packet::codecs::WsIoPacketCodec,
+    types::hashers::FxDashMap,
+};
+
+// Types
+type AckResult = Result<Option<Bytes>, WsIoRemoteError>;
+
+// Structs
+/// Ack waiting for the response of the peer, unregistered when dropped so that abandoned waits do not leak.
+pub struct WsIoPendingAck<'a> {
+    ack_id: u32,
+    ack_rx: Receiver<AckResult>,
+    registry: &'a WsIoAckRegistry,
+}
+
+impl Drop for WsIoPendingAck<'_> {
+    fn drop(&mut self) {
+        self.registry.remove(self.ack_id);
+    }
+}
+
+impl WsIoPendingAck<'_> {
+    // Public methods
+    #[inline]
+    pub fn ack_id(&self) -> u32 {
+        self.ack_id
+    }
+
+    pub async fn wait<R: DeserializeOwned + 'static>(
+        mut self,
+        duration: Duration,
+        packet_codec: &WsIoPacketCodec,
+    ) -> Result<R> {
+        let ack_id = self.ack_id;
+        let data = match timeout(duration, &mut self.ack_rx).await {
+            Ok(Ok(result)) => result?,
+            Ok(Err(_)) => bail!("Ack {ack_id} dropped before a response was received"),
+            Err(_) => bail!("Ack {ack_id} timed out after {duration:?}"),
+        };
+
+        packet_codec.decode_data(&data.ok_or_else(|| anyhow!("Ack {ack_id} response missing data"))?)
+    }
+}
+
+pub struct WsIoAckRegistry {
+    next_ack_id: AtomicU32,
+    pending_acks: FxDashMap<u32, Sender<AckResult>>,
+}
+
+impl Default for WsIoAckRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WsIoAckRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            next_ack_id: AtomicU32::new(0),
+            pending_acks: FxDashMap::default(),
+        }
+    }
+
+    // Public methods
+    #[inline]
+    pub fn clear(&self) {
+        self.pending_acks.clear();
+    }
+
+    /// Registers a pending ack, removed again once its handle is dropped.
+    #[inline]
+    pub fn register(&self) -> WsIoPendingAck<'_> {
+        let (ack_tx, ack_rx) = channel();
+        let ack_id = self.next_ack_id.fetch_add(1, Ordering::Relaxed);
+        self.pending_acks.insert(ack_id, ack_tx);
+        WsIoPendingAck {
+            ack_id,
+            ack_rx,
+            registry: self,
+        }
+    }
+
+    #[inline]
+    pub fn reject(&self, ack_id: u32, error: WsIoRemoteError) {
+        if let Some((_, ack_tx)) = self.pending_acks.remove(&ack_id) {
+            let _ = ack_tx.send(Err(error));
+        }
+    }
+
+    #[inline]
+    pub fn remove(&self, ack_id: u32) {
+        self.pending_acks.remove(&ack_id);
+    }
+
+    #[inline]
+    pub fn resolve(&self, ack_id: u32, data: Option<Bytes>) {
+        if let Some((_, ack_tx)) = self.pending_acks.remove(&ack_id) {
+            let _ = ack_tx.send(Ok(data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn removes_acks_whose_wait_is_dropped() {
+        let ack_registry = WsIoAckRegistry::new();
+        let pending_ack = ack_registry.register();
+        assert_eq!(ack_registry.pending_acks.len(), 1);
+
+        drop(pending_ack);
+        assert!(ack_registry.pending_acks.is_empty());
+
+        let pending_ack = ack_registry.register();
+        let wait = pending_ack.wait::<u32>(Duration::from_secs(60), &WsIoPacketCodec::SerdeJson);
+        drop(wait);
+        assert!(ack_registry.pending_acks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolves_and_times_out_acks() {
+        let ack_registry = WsIoAckRegistry::new();
+        let pending_ack = ack_registry.register();
+        ack_registry.resolve(pending_ack.ack_id(), Some(Bytes::from_static(b"7")));
+        let data = pending_ack
+            .wait::<u32>(Duration::from_secs(60), &WsIoPacketCodec::SerdeJson)
+            .await
+            .unwrap();
+
+        assert_eq!(data, 7);
+
+        let pending_ack = ack_registry.register();
+        assert!(
+            pending_ack
+                .wait::<u32>(Duration::from_millis(1), &WsIoPacketCodec::SerdeJson)
+                .await
+                .is_err()
+        );
+
+        assert!(ack_registry.pending_acks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_pending_acks_when_cleared() {
+        let ack_registry = WsIoAckRegistry::new();
+        let pending_ack = ack_registry.register();
+        ack_registry.clear();
+        assert!(
+            pending_ack
+                .wait::<u32>(Duration::from_secs(60), &WsIoPacketCodec::SerdeJson)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{
+        Debug,
+        Display,
+        Formatter,
+        Result as FmtResult,
+    },
+};
+
+use anyhow::Result;
+use serde::{
+    Serialize,
+    de::DeserializeOwned,
+};
+use tokio::time::error::Elapsed;
+
+use crate::packet::{
+    WsIoPacket,
+    codecs::WsIoPacketCodec,
+};
+
+// Structs
+/// Error that is reported to the peer as an error packet.
+///
+/// Return it from handshake or event handlers to tell the peer why the request was rejected; any other error is
+/// reported as [`INTERNAL_ERROR_CODE`] without exposing its details.
+pub struct WsIoError {
+    code: String,
+    data: Option<Box<dyn erased_serde::Serialize + Send + Sync>>,
+    message: String,
+}
+
+impl Debug for WsIoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("WsIoError")
+            .field("code", &self.code)
+            .field("has_data", &self.data.is_some())
+            .field("message", &self.message)
+            .finish()
+    }
+}
+
+impl Display for WsIoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl Error for WsIoError {}
+
+impl WsIoError {
+    #[inline]
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            data: None,
+            message: message.into(),
+        }
+    }
+
+    // Public methods
+    #[inline]
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[inline]
+    pub fn with_data<D: Serialize + Send + Sync + 'static>(mut self, data: D) -> Self {
+        self.data = Some(Box::new(data));
+        self
+    }
+}
+
+/// Error reported by the peer through an error packet.
+#[derive(Clone, Debug)]
+pub struct WsIoRemoteError {
+    code: String,
+    data: Option<Vec<u8>>,
+    message: String,
+    packet_codec: WsIoPacketCodec,
+}
+
+impl Display for WsIoRemoteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl Error for WsIoRemoteError {}
+
+impl WsIoRemoteError {
+    #[inline]
+    pub fn from_packet(packet: WsIoPacket, packet_codec: &WsIoPacketCodec) -> Self {
+        Self {
+            code: packet.key.map_or_else(|| INTERNAL_ERROR_CODE.into(), Cow::into_owned),
+            data: packet.data.map(Cow::into_owned),
+            message: packet.message.map(Cow::into_owned).unwrap_or_default(),
+            packet_codec: packet_codec.clone(),
+        }
+    }
+
+    // Public methods
+    #[inline]
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    #[inline]
+    pub fn data<D: DeserializeOwned + 'static>(&self) -> Result<Option<D>> {
+        self.data
+            .as_deref()
+            .map(|bytes| self.packet_codec.decode_data(bytes))
+            .transpose()
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+// Constants/Statics
+pub const INTERNAL_ERROR_CODE: &str = "internal_error";
+pub const INVALID_DATA_ERROR_CODE: &str = "invalid_data";
+pub const TIMEOUT_ERROR_CODE: &str = "timeout";
+pub const UNSUPPORTED_PROTOCOL_VERSION_ERROR_CODE: &str = "unsupported_protocol_version";
+
+// Functions
+/// Builds the error packet reporting `error` to the peer.
+pub fn encode_error_packet<'a>(error: &'a anyhow::Error, packet_codec: &WsIoPacketCodec) -> Result<WsIoPacket<'a>> {
+    if let Some(error) = error.downcast_ref::<WsIoError>() {
+        return Ok(WsIoPacket::new_error(
+            &error.code,
+            &error.message,
+            error
+                .data
+                .as_deref()
+                .map(|data| packet_codec.encode_data(&data))
+                .transpose()?,
+        ));
+    }
+
+    Ok(match error.is::<Elapsed>() {
+        true => WsIoPacket::new_error(TIMEOUT_ERROR_CODE, "Operation timed out", None),
+        false => WsIoPacket::new_error(INTERNAL_ERROR_CODE, "Internal error", None),
+    })
+}
@@ -0,0 +1,29 @@
+// Enums
+/// Order in which the events received from a single peer are handled.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WsIoEventDispatchMode {
+    /// Every event and each of its handlers run concurrently.
+    #[default]
+    Concurrent,
+
+    /// Events are handled one at a time in arrival order, running their handlers one after another.
+    Sequential,
+
+    /// Like [`Sequential`](Self::Sequential), but only events with the same name wait for each other.
+    SequentialPerEvent,
+}
+
+/// Action taken when an event arrives while the maximum number of events are already being handled.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WsIoEventOverflowPolicy {
+    /// Queue the event until an in-flight event completes. Once as many events are queued, stop reading from the peer
+    /// until one of them starts, propagating backpressure to the socket.
+    ///
+    /// Other packets, such as the acks and stream credits that in-flight handlers may wait for, keep being read while
+    /// events are queued.
+    #[default]
+    Backpressure,
+
+    /// Close the connection with the peer.
+    Disconnect,
+}
@@ -0,0 +1,60 @@
+use std::{
+    error::Error,
+    fmt::{
+        Display,
+        Formatter,
+        Result as FmtResult,
+    },
+    pin::Pin,
+    sync::Arc,
+};
+
+use anyhow::Result;
+
+use super::registry::WsIoEventPayload;
+
+// Types
+pub(crate) type EventChain = Box<dyn FnOnce(WsIoEventPayload) -> EventChainFuture + Send + 'static>;
+pub(crate) type EventChainFuture = Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>;
+
+/// Interceptor wrapping the handling of every event, receiving the connection context, the event name, its payload
+/// and the rest of the chain.
+///
+/// Calling [`WsIoEventNext::run`] passes the event on, returning without calling it silently discards the event, and
+/// returning an error rejects it: the error is sent to the peer as if a handler had failed.
+pub type WsIoEventInterceptor<C> =
+    Arc<dyn Fn(Arc<C>, String, WsIoEventPayload, WsIoEventNext) -> EventChainFuture + Send + Sync + 'static>;
+
+// Structs
+/// Failure of an event handler passed up the interceptor chain, already replied to the peer and reported.
+#[derive(Debug)]
+pub(crate) struct EventHandlerError(pub(crate) String);
+
+impl Display for EventHandlerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for EventHandlerError {}
+
+/// Rest of the interceptor chain of an event, ending with its handlers.
+pub struct WsIoEventNext(EventChain);
+
+impl WsIoEventNext {
+    #[inline]
+    pub(crate) fn new(event_chain: EventChain) -> Self {
+        Self(event_chain)
+    }
+
+    // Public methods
+    /// Runs the remaining interceptors and then the handlers of the event with `payload`, which may differ from the
+    /// one the interceptor received.
+    ///
+    /// Resolves once every handler completed, failing if one of them failed. Such failures were already replied to
+    /// the peer and reported, returning them from the interceptor does not send them again.
+    #[inline]
+    pub async fn run(self, payload: WsIoEventPayload) -> Result<()> {
+        (self.0)(payload).await
+    }
+}
@@ -1 +1,5 @@
+pub mod dispatch;
+pub mod interceptor;
+pub mod pattern;
 pub mod registry;
+pub mod subscription;
@@ -0,0 +1,182 @@
+use anyhow::{
+    Result,
+    bail,
+};
+use regex::{
+    Regex,
+    escape,
+};
+
+// Structs
+/// Compiled event route such as `chat:{room}:message` or `orders.*`.
+///
+/// `{name}` captures one or more characters other than `.`, `:` and `/`, while `*` captures one or more characters
+/// of any kind and is exposed under the `*` parameter name. Captures are as short as possible.
+///
+/// Routes are compiled to a regular expression, so matching takes linear time in the length of the event name
+/// whatever the number of captures.
+pub(crate) struct EventPattern {
+    param_names: Vec<String>,
+    regex: Regex,
+}
+
+impl EventPattern {
+    pub(crate) fn parse(pattern: &str) -> Result<Self> {
+        let mut param_names = Vec::new();
+        let mut regex = String::from("(?s)^");
+        let mut chars = pattern.chars();
+        let mut follows_capture = false;
+        while let Some(char) = chars.next() {
+            let (param_name, capture) = match char {
+                '*' => ("*".into(), "(.+?)"),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => bail!("Unterminated parameter in event pattern '{pattern}'"),
+                            Some(char) => name.push(char),
+                        }
+                    }
+
+                    if name.is_empty() {
+                        bail!("Empty parameter name in event pattern '{pattern}'");
+                    }
+
+                    (name, "([^.:/]+?)")
+                }
+                _ => {
+                    regex.push_str(&escape(char.encode_utf8(&mut [0; 4])));
+                    follows_capture = false;
+                    continue;
+                }
+            };
+
+            if follows_capture {
+                bail!("Event pattern '{pattern}' must separate parameters and wildcards with literal text");
+            }
+
+            param_names.push(param_name);
+            regex.push_str(capture);
+            follows_capture = true;
+        }
+
+        regex.push('$');
+        Ok(Self {
+            param_names,
+            regex: Regex::new(&regex)?,
+        })
+    }
+
+    // Protected methods
+    pub(crate) fn captures(&self, event: &str) -> Option<WsIoEventParams> {
+        let captures = self.regex.captures(event)?;
+        Some(WsIoEventParams(
+            self.param_names
+                .iter()
+                .zip(captures.iter().skip(1))
+                .filter_map(|(name, capture)| Some((name.clone(), capture?.as_str().to_owned())))
+                .collect(),
+        ))
+    }
+}
+
+/// Parameters captured from the event name by a pattern route.
+#[derive(Clone, Debug, Default)]
+pub struct WsIoEventParams(Vec<(String, String)>);
+
+impl WsIoEventParams {
+    // Public methods
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Text matched by the first `*` of the pattern.
+    #[inline]
+    pub fn wildcard(&self) -> Option<&str> {
+        self.get("*")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(pattern: &str, event: &str) -> Option<Vec<(String, String)>> {
+        EventPattern::parse(pattern)
+            .unwrap()
+            .captures(event)
+            .map(|params| params.iter().map(|(name, value)| (name.into(), value.into())).collect())
+    }
+
+    #[test]
+    fn captures_params_and_wildcards() {
+        let params = EventPattern::parse("chat:{room}:message")
+            .unwrap()
+            .captures("chat:lobby:message")
+            .unwrap();
+        assert_eq!(params.get("room"), Some("lobby"));
+
+        let params = EventPattern::parse("orders.*")
+            .unwrap()
+            .captures("orders.eu.created")
+            .unwrap();
+        assert_eq!(params.wildcard(), Some("eu.created"));
+    }
+
+    #[test]
+    fn params_do_not_cross_separators() {
+        assert!(captures("chat:{room}", "chat:a:b").is_none());
+        assert!(captures("chat:{room}", "chat:a.b").is_none());
+        assert!(captures("chat:{room}", "chat:a/b").is_none());
+        assert_eq!(
+            captures("*-{id}", "a-b.c-d"),
+            Some(vec![("*".into(), "a-b.c".into()), ("id".into(), "d".into())])
+        );
+    }
+
+    #[test]
+    fn captures_are_as_short_as_possible() {
+        assert_eq!(
+            captures("*.*", "a.b.c"),
+            Some(vec![("*".into(), "a".into()), ("*".into(), "b.c".into())])
+        );
+    }
+
+    #[test]
+    fn captures_must_not_be_empty() {
+        assert!(captures("orders.*", "orders.").is_none());
+        assert!(captures("chat:{room}:message", "chat::message").is_none());
+    }
+
+    #[test]
+    fn literals_match_exactly() {
+        assert!(captures("a.b", "a.b").is_some());
+        assert!(captures("a.b", "axb").is_none());
+        assert!(captures("a.b", "a.bc").is_none());
+        assert!(captures("(a)+", "(a)+").is_some());
+    }
+
+    #[test]
+    fn matches_long_adversarial_names() {
+        let event = "a".repeat(1 << 20);
+        assert!(captures("a*b*c*d", &event).is_none());
+        assert!(captures("a*a", &event).is_some());
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        for pattern in ["chat:{room", "chat:{}", "chat:{a{b}", "{a}{b}", "**", "{a}*"] {
+            assert!(EventPattern::parse(pattern).is_err(), "{pattern}");
+        }
+    }
+}
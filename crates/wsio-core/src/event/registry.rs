@@ -3,159 +3,916 @@ use std::{
         Any,
         TypeId,
     },
-    collections::hash_map::Entry,
+    collections::{
+        BTreeMap,
+        VecDeque,
+    },
     marker::PhantomData,
     pin::Pin,
     sync::{
         Arc,
         LazyLock,
         atomic::{
-            AtomicU32,
+            AtomicBool,
+            AtomicU64,
+            AtomicUsize,
             Ordering,
         },
     },
+    time::Duration,
 };
 
-use anyhow::Result;
-use parking_lot::RwLock;
-use serde::de::DeserializeOwned;
+use anyhow::{
+    Result,
+    bail,
+};
+use bytes::Bytes;
+use parking_lot::{
+    Mutex,
+    RwLock,
+};
+use serde::{
+    Serialize,
+    de::DeserializeOwned,
+};
+use tokio::{
+    sync::{
+        OwnedSemaphorePermit,
+        Semaphore,
+        mpsc::unbounded_channel,
+        oneshot::channel,
+    },
+    time::timeout,
+};
 
+use super::{
+    dispatch::{
+        WsIoEventDispatchMode,
+        WsIoEventOverflowPolicy,
+    },
+    interceptor::{
+        EventChain,
+        EventChainFuture,
+        EventHandlerError,
+        WsIoEventInterceptor,
+        WsIoEventNext,
+    },
+    pattern::{
+        EventPattern,
+        WsIoEventParams,
+    },
+    subscription::WsIoSubscription,
+};
 use crate::{
+    error::{
+        INVALID_DATA_ERROR_CODE,
+        WsIoError,
+    },
     packet::codecs::WsIoPacketCodec,
-    traits::task::spawner::TaskSpawner,
+    traits::{
+        ack::sender::AckSender,
+        error::{
+            reporter::EventErrorReporter,
+            sender::ErrorSender,
+        },
+        task::spawner::TaskSpawner,
+    },
     types::hashers::FxHashMap,
 };
 
 // Types
-type DataDecoder = fn(&[u8], &WsIoPacketCodec) -> Result<Arc<dyn Any + Send + Sync>>;
+type AnyHandler<C> = Arc<
+    dyn Fn(Arc<C>, String, Option<Bytes>) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+type AnyHandlers<C> = Arc<RwLock<BTreeMap<u64, AnyHandler<C>>>>;
+type DataDecoder = fn(&WsIoEventPayload, &WsIoPacketCodec) -> Result<Arc<dyn Any + Send + Sync>>;
 type Handler<C> = Arc<
-    dyn Fn(Arc<C>, Arc<dyn Any + Send + Sync>) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>
+    dyn Fn(
+            Arc<C>,
+            Arc<dyn Any + Send + Sync>,
+            WsIoEventParams,
+            Option<WsIoPacketCodec>,
+        ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>>> + Send + 'static>>
         + Send
         + Sync
         + 'static,
 >;
 
+type EventEntries<C> = Arc<RwLock<FxHashMap<String, Arc<EventEntry<C>>>>>;
+type EventJob = Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>;
+type EventJobQueues = Arc<Mutex<FxHashMap<String, VecDeque<EventJob>>>>;
+
+// Enums
+/// Permit allowing the handlers of an event to run, possibly still to be acquired.
+enum InFlightEventPermit {
+    Acquired(OwnedSemaphorePermit),
+    Waiting {
+        in_flight_event_semaphore: Arc<Semaphore>,
+
+        /// Slot among the events waiting for a permit, released once it is acquired.
+        waiting_event_permit: OwnedSemaphorePermit,
+    },
+}
+
+impl InFlightEventPermit {
+    // Private methods
+    async fn acquire(self) -> Result<OwnedSemaphorePermit> {
+        match self {
+            Self::Acquired(permit) => Ok(permit),
+            Self::Waiting {
+                in_flight_event_semaphore,
+                waiting_event_permit,
+            } => {
+                let permit = in_flight_event_semaphore.acquire_owned().await?;
+                drop(waiting_event_permit);
+                Ok(permit)
+            }
+        }
+    }
+}
+
 // Structs
+/// Decoded payload handed to attachment handlers together with the attachments of the event.
+struct AttachedEventData<D> {
+    attachments: Vec<Bytes>,
+    data: Arc<D>,
+}
+
+/// Handling of a received event once its interceptors passed it on, run by [`WsIoEventNext`].
+struct EventDispatch<C, S> {
+    ack_claimed: Arc<AtomicBool>,
+    ack_id: Option<u32>,
+    any_handlers: AnyHandlers<C>,
+    ctx: Arc<C>,
+    dispatch_mode: WsIoEventDispatchMode,
+    event: Arc<str>,
+    event_entries: EventEntries<C>,
+    handler_timeout: Option<Duration>,
+    packet_codec: WsIoPacketCodec,
+    pattern_entries: EventEntries<C>,
+
+    /// Key, entry and captured params of every route matching the event.
+    routes: Vec<(String, Arc<EventEntry<C>>, WsIoEventParams)>,
+    task_spawner: Arc<S>,
+}
+
+impl<C: AckSender + ErrorSender + EventErrorReporter, S: TaskSpawner> EventDispatch<C, S> {
+    // Private methods
+    async fn run(self, payload: WsIoEventPayload) -> Result<()> {
+        let mut handler_futures = Vec::<EventChainFuture>::new();
+        let any_handlers = self.any_handlers.read().values().cloned().collect::<Vec<_>>();
+        for any_handler in any_handlers {
+            let ctx = self.ctx.clone();
+            let event = self.event.clone();
+            let handler_timeout = self.handler_timeout;
+            let packet_data = payload.data.clone();
+            handler_futures.push(Box::pin(async move {
+                let any_handler_future = any_handler(ctx.clone(), event.to_string(), packet_data);
+                if let Err(err) = with_handler_timeout(any_handler_future, &event, handler_timeout).await {
+                    let handler_error = EventHandlerError(format!("{err:#}"));
+                    ctx.report_event_error(&event, err).await;
+                    bail!(handler_error);
+                }
+
+                Ok(())
+            }));
+        }
+
+        let mut targets = Vec::new();
+        for (key, event_entry, params) in &self.routes {
+            let event_entries = match event_entry.pattern.is_some() {
+                true => &self.pattern_entries,
+                false => &self.event_entries,
+            };
+
+            for (handler_id, handler_entry) in claim_handlers(event_entries, key, event_entry, payload.data.is_some()) {
+                targets.push((handler_id, handler_entry, params.clone()));
+            }
+        }
+
+        // Run the handlers of every route in registration order
+        targets.sort_unstable_by_key(|(handler_id, ..)| *handler_id);
+
+        // Decode the payload once per distinct data type across all routes, remembering failures as `None`. Each
+        // failure is reported locally, but the peer is only told the payload is invalid when no handler could decode
+        // it
+        let mut decoded_data = FxHashMap::<TypeId, Option<Arc<dyn Any + Send + Sync>>>::default();
+        for (_, handler_entry, _) in &targets {
+            if decoded_data.contains_key(&handler_entry.data_type_id) {
+                continue;
+            }
+
+            let data = match (handler_entry.data_decoder)(&payload, &self.packet_codec) {
+                Ok(data) => Some(data),
+                Err(err) => {
+                    self.ctx
+                        .report_event_error(&self.event, err.context("Failed to decode event data"))
+                        .await;
+
+                    None
+                }
+            };
+
+            decoded_data.insert(handler_entry.data_type_id, data);
+        }
+
+        // The first handler to finish or fail claims the ack, so the peer receives exactly one reply per event
+        if !decoded_data.is_empty() && decoded_data.values().all(Option::is_none) {
+            let error = WsIoError::new(INVALID_DATA_ERROR_CODE, "Failed to decode event data");
+            let _ = self
+                .ctx
+                .send_error(&error.into(), claim_ack_id(self.ack_id, &self.ack_claimed))
+                .await;
+        }
+
+        for (_, handler_entry, params) in targets {
+            let Some(Some(data)) = decoded_data.get(&handler_entry.data_type_id) else {
+                continue;
+            };
+
+            if (**data).type_id() != handler_entry.data_type_id {
+                continue;
+            }
+
+            let ack_claimed = self.ack_claimed.clone();
+            let ack_id = self.ack_id;
+            let ctx = self.ctx.clone();
+            let data = data.clone();
+            let event = self.event.clone();
+            let handler = handler_entry.handler;
+            let handler_timeout = handler_entry.timeout.or(self.handler_timeout);
+            let response_packet_codec = ack_id.map(|_| self.packet_codec.clone());
+            handler_futures.push(Box::pin(async move {
+                // Reply to the peer if it is waiting for an ack and no other handler replied, and report failures
+                let handler_future = handler(ctx.clone(), data, params, response_packet_codec);
+                match with_handler_timeout(handler_future, &event, handler_timeout).await {
+                    Ok(response_data) => {
+                        ctx.clear_event_errors();
+                        if let Some(ack_id) = claim_ack_id(ack_id, &ack_claimed) {
+                            let _ = ctx.send_ack(ack_id, response_data).await;
+                        }
+
+                        Ok(())
+                    }
+                    Err(err) => {
+                        let _ = ctx.send_error(&err, claim_ack_id(ack_id, &ack_claimed)).await;
+                        let handler_error = EventHandlerError(format!("{err:#}"));
+                        ctx.report_event_error(&event, err).await;
+                        bail!(handler_error);
+                    }
+                }
+            }));
+        }
+
+        // Handlers are awaited in registration order in the sequential modes and spawned otherwise, the event failing
+        // with the first handler that failed
+        let mut result = Ok(());
+        match self.dispatch_mode {
+            WsIoEventDispatchMode::Concurrent => {
+                let (result_tx, mut result_rx) = unbounded_channel();
+                for handler_future in handler_futures {
+                    let result_tx = result_tx.clone();
+                    self.task_spawner.spawn_task(async move {
+                        let _ = result_tx.send(handler_future.await);
+                        Ok(())
+                    });
+                }
+
+                drop(result_tx);
+                while let Some(handler_result) = result_rx.recv().await {
+                    result = result.and(handler_result);
+                }
+            }
+            _ => {
+                for handler_future in handler_futures {
+                    result = result.and(handler_future.await);
+                }
+            }
+        }
+
+        result
+    }
+}
+
 struct EventEntry<C> {
+    /// Handlers keyed by their id, so that iterating them follows the registration order.
+    handlers: RwLock<BTreeMap<u64, HandlerEntry<C>>>,
+    pattern: Option<EventPattern>,
+}
+
+/// Drops the queue of a sequential worker whose task was cancelled, so the next event spawns a fresh worker.
+struct EventJobWorkerGuard {
+    armed: bool,
+    event_job_queues: EventJobQueues,
+    key: String,
+}
+
+impl EventJobWorkerGuard {
+    // Private methods
+    #[inline]
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for EventJobWorkerGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            self.event_job_queues.lock().remove(&self.key);
+        }
+    }
+}
+
+struct HandlerEntry<C> {
     data_decoder: DataDecoder,
     data_type_id: TypeId,
-    handlers: RwLock<FxHashMap<u32, Handler<C>>>,
+    handler: Handler<C>,
+    once: bool,
+    timeout: Option<Duration>,
+}
+
+impl<C> HandlerEntry<C> {
+    #[inline]
+    fn new<D: DeserializeOwned + Send + Sync + 'static>(
+        handler: Handler<C>,
+        once: bool,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            data_decoder: decode_data_as_any_arc::<D>,
+            data_type_id: TypeId::of::<D>(),
+            handler,
+            once,
+            timeout,
+        }
+    }
+
+    // Private methods
+    #[inline]
+    fn accepts_missing_data(&self) -> bool {
+        self.data_type_id == TypeId::of::<()>() || self.data_type_id == TypeId::of::<RawEventData>()
+    }
+}
+
+impl<C> Clone for HandlerEntry<C> {
+    fn clone(&self) -> Self {
+        Self {
+            data_decoder: self.data_decoder,
+            data_type_id: self.data_type_id,
+            handler: self.handler.clone(),
+            once: self.once,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// Undecoded payload handed to raw handlers together with the codec it was encoded with.
+struct RawEventData {
+    data: Option<Bytes>,
+    packet_codec: WsIoPacketCodec,
+}
+
+/// Marks reading as paused until dropped.
+struct ReadingPause<'a>(&'a AtomicUsize);
+
+impl Drop for ReadingPause<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Payload of a received event, its encoded data and the binary attachments sent alongside it.
+#[derive(Clone, Debug, Default)]
+pub struct WsIoEventPayload {
+    pub attachments: Vec<Bytes>,
+    pub data: Option<Bytes>,
 }
 
-pub struct WsIoEventRegistry<C: Send + Sync + 'static, S: TaskSpawner> {
+pub struct WsIoEventRegistry<C: AckSender + ErrorSender + EventErrorReporter, S: TaskSpawner> {
     _task_spawner: PhantomData<S>,
-    event_entries: RwLock<FxHashMap<String, Arc<EventEntry<C>>>>,
-    next_handler_id: AtomicU32,
+    any_handlers: AnyHandlers<C>,
+    dispatch_mode: WsIoEventDispatchMode,
+    event_entries: EventEntries<C>,
+    event_interceptors: Arc<[WsIoEventInterceptor<C>]>,
+    event_job_queues: EventJobQueues,
+    event_overflow_policy: WsIoEventOverflowPolicy,
+    handler_timeout: Option<Duration>,
+    in_flight_event_semaphore: Option<Arc<Semaphore>>,
+    next_handler_id: AtomicU64,
+
+    /// Number of events currently pausing reading while they wait for room among the waiting events.
+    paused_reads: AtomicUsize,
+    pattern_entries: EventEntries<C>,
+
+    /// Number of times reading was paused so far.
+    read_pauses: AtomicU64,
+    waiting_event_semaphore: Option<Arc<Semaphore>>,
 }
 
-impl<C: Send + Sync + 'static, S: TaskSpawner> Default for WsIoEventRegistry<C, S> {
+impl<C: AckSender + ErrorSender + EventErrorReporter, S: TaskSpawner> Default for WsIoEventRegistry<C, S> {
     fn default() -> Self {
-        Self::new()
+        Self::new(
+            WsIoEventDispatchMode::default(),
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        )
     }
 }
 
-impl<C: Send + Sync + 'static, S: TaskSpawner> WsIoEventRegistry<C, S> {
+impl<C: AckSender + ErrorSender + EventErrorReporter, S: TaskSpawner> WsIoEventRegistry<C, S> {
     #[inline]
-    pub fn new() -> Self {
+    pub fn new(
+        dispatch_mode: WsIoEventDispatchMode,
+        handler_timeout: Option<Duration>,
+        max_in_flight_events: Option<usize>,
+        event_overflow_policy: WsIoEventOverflowPolicy,
+        event_interceptors: Vec<WsIoEventInterceptor<C>>,
+    ) -> Self {
         Self {
             _task_spawner: PhantomData,
-            event_entries: RwLock::new(FxHashMap::default()),
-            next_handler_id: AtomicU32::new(0),
+            any_handlers: Arc::new(RwLock::new(BTreeMap::new())),
+            dispatch_mode,
+            event_entries: Arc::new(RwLock::new(FxHashMap::default())),
+            event_interceptors: event_interceptors.into(),
+            event_job_queues: Arc::new(Mutex::new(FxHashMap::default())),
+            event_overflow_policy,
+            handler_timeout,
+            in_flight_event_semaphore: max_in_flight_events
+                .map(|max_in_flight_events| Arc::new(Semaphore::new(max_in_flight_events))),
+            next_handler_id: AtomicU64::new(0),
+            paused_reads: AtomicUsize::new(0),
+            pattern_entries: Arc::new(RwLock::new(FxHashMap::default())),
+            read_pauses: AtomicU64::new(0),
+            waiting_event_semaphore: max_in_flight_events
+                .map(|max_in_flight_events| Arc::new(Semaphore::new(max_in_flight_events))),
         }
     }
 
-    // Public methods
+    // Private methods
+    fn enqueue_event_job(&self, key: &str, event_job: EventJob, task_spawner: &Arc<S>) {
+        let mut event_job_queues = self.event_job_queues.lock();
+        if let Some(event_job_queue) = event_job_queues.get_mut(key) {
+            event_job_queue.push_back(event_job);
+            return;
+        }
+
+        event_job_queues.insert(key.into(), VecDeque::from([event_job]));
+        drop(event_job_queues);
+
+        // The worker drains the queue and removes it once empty, both under the lock, so no job is left behind
+        let event_job_queues = self.event_job_queues.clone();
+        let key = key.to_owned();
+        let mut guard = EventJobWorkerGuard {
+            armed: true,
+            event_job_queues: event_job_queues.clone(),
+            key: key.clone(),
+        };
+
+        task_spawner.spawn_task(async move {
+            loop {
+                let event_job = {
+                    let mut event_job_queues = event_job_queues.lock();
+                    match event_job_queues
+                        .get_mut(&key)
+                        .and_then(|event_job_queue| event_job_queue.pop_front())
+                    {
+                        Some(event_job) => event_job,
+                        None => {
+                            event_job_queues.remove(&key);
+                            guard.disarm();
+                            return Ok(());
+                        }
+                    }
+                };
+
+                let _ = event_job.await;
+            }
+        });
+    }
+
+    fn insert_handler(
+        &self,
+        key: &str,
+        pattern: Option<EventPattern>,
+        handler_entry: HandlerEntry<C>,
+    ) -> WsIoSubscription {
+        let event_entries_lock = match pattern.is_some() {
+            true => &self.pattern_entries,
+            false => &self.event_entries,
+        };
+
+        // Handlers of the same event may expect different payload types, each decoded separately on dispatch
+        let mut event_entries = event_entries_lock.write();
+        let event_entry = event_entries.entry(key.into()).or_insert_with(|| {
+            Arc::new(EventEntry {
+                handlers: RwLock::new(BTreeMap::new()),
+                pattern,
+            })
+        });
+
+        let handler_id = self.next_handler_id.fetch_add(1, Ordering::Relaxed);
+        event_entry.handlers.write().insert(handler_id, handler_entry);
+
+        // Only hold weak references so a subscription outliving its registry does nothing when dropped
+        let event_entry = Arc::downgrade(event_entry);
+        let event_entries = Arc::downgrade(event_entries_lock);
+        let key = key.to_owned();
+        WsIoSubscription::new(handler_id, move || {
+            let (Some(event_entry), Some(event_entries)) = (event_entry.upgrade(), event_entries.upgrade()) else {
+                return;
+            };
+
+            event_entry.handlers.write().remove(&handler_id);
+            remove_event_entry_if_empty(&event_entries, &key, &event_entry);
+        })
+    }
+
     #[inline]
-    pub fn dispatch_event_packet(
+    fn pause_reading(&self) -> ReadingPause<'_> {
+        self.read_pauses.fetch_add(1, Ordering::Relaxed);
+        self.paused_reads.fetch_add(1, Ordering::AcqRel);
+        ReadingPause(&self.paused_reads)
+    }
+
+    #[inline]
+    fn remove_event_entry_if_empty(&self, key: &str, event_entry: &Arc<EventEntry<C>>) {
+        let event_entries = match event_entry.pattern.is_some() {
+            true => &self.pattern_entries,
+            false => &self.event_entries,
+        };
+
+        remove_event_entry_if_empty(event_entries, key, event_entry);
+    }
+
+    // Public methods
+    pub async fn dispatch_event_packet(
         &self,
         ctx: Arc<C>,
         event: &str,
         packet_codec: &WsIoPacketCodec,
-        packet_data: Option<Vec<u8>>,
+        payload: WsIoEventPayload,
+        ack_id: Option<u32>,
         task_spawner: &Arc<S>,
-    ) {
-        let Some(event_entry) = self.event_entries.read().get(event).cloned() else {
-            return;
-        };
+    ) -> Result<()> {
+        // Hold a permit until the interceptors and every handler of the event complete, waiting or failing when none
+        // is left. Events wait for it in their job rather than here, so that the acks, pongs and stream packets that
+        // in-flight handlers may be waiting for keep being read; only once as many events are waiting is reading
+        // paused
+        let in_flight_event_permit = match &self.in_flight_event_semaphore {
+            Some(in_flight_event_semaphore) => Some(match in_flight_event_semaphore.clone().try_acquire_owned() {
+                Ok(permit) => InFlightEventPermit::Acquired(permit),
+                Err(_) => match (&self.waiting_event_semaphore, self.event_overflow_policy) {
+                    (Some(waiting_event_semaphore), WsIoEventOverflowPolicy::Backpressure) => {
+                        let waiting_event_permit = match waiting_event_semaphore.clone().try_acquire_owned() {
+                            Ok(permit) => permit,
+                            Err(_) => {
+                                let _reading_pause = self.pause_reading();
+                                waiting_event_semaphore.clone().acquire_owned().await?
+                            }
+                        };
 
-        let packet_codec = *packet_codec;
-        let task_spawner_clone = task_spawner.clone();
-        task_spawner.spawn_task(async move {
-            let data = match packet_data {
-                Some(bytes) => match (event_entry.data_decoder)(&bytes, &packet_codec) {
-                    Ok(data) => data,
-                    Err(_) => return Ok(()),
+                        InFlightEventPermit::Waiting {
+                            in_flight_event_semaphore: in_flight_event_semaphore.clone(),
+                            waiting_event_permit,
+                        }
+                    }
+                    _ => bail!("Too many in-flight events, received '{event}'"),
                 },
-                None => EMPTY_EVENT_DATA_ANY_ARC.clone(),
+            }),
+            None => None,
+        };
+
+        // Match the routes of the event now, but only claim their handlers once the interceptors passed it on
+        let mut routes = Vec::new();
+        let event_entry = self.event_entries.read().get(event).cloned();
+        if let Some(event_entry) = event_entry {
+            routes.push((event.to_owned(), event_entry, WsIoEventParams::default()));
+        }
+
+        routes.extend(self.pattern_entries.read().iter().filter_map(|(pattern, event_entry)| {
+            let params = event_entry.pattern.as_ref()?.captures(event)?;
+            Some((pattern.clone(), event_entry.clone(), params))
+        }));
+
+        if routes.is_empty() && self.any_handlers.read().is_empty() && self.event_interceptors.is_empty() {
+            return Ok(());
+        }
+
+        // Sequential events share a single queue unless they are only ordered per event name
+        let dispatch_mode = self.dispatch_mode;
+        let event_job_key = match dispatch_mode {
+            WsIoEventDispatchMode::SequentialPerEvent => event,
+            _ => "",
+        };
+
+        let ack_claimed = Arc::new(AtomicBool::new(false));
+        let event = Arc::<str>::from(event);
+        let event_dispatch = EventDispatch {
+            ack_claimed: ack_claimed.clone(),
+            ack_id,
+            any_handlers: self.any_handlers.clone(),
+            ctx: ctx.clone(),
+            dispatch_mode,
+            event: event.clone(),
+            event_entries: self.event_entries.clone(),
+            handler_timeout: self.handler_timeout,
+            packet_codec: packet_codec.clone(),
+            pattern_entries: self.pattern_entries.clone(),
+            routes,
+            task_spawner: task_spawner.clone(),
+        };
+
+        let event_interceptors = self.event_interceptors.clone();
+        let event_job = Box::pin(async move {
+            let _in_flight_event_permit = match in_flight_event_permit {
+                Some(in_flight_event_permit) => Some(in_flight_event_permit.acquire().await?),
+                None => None,
             };
 
-            let handlers = event_entry.handlers.read().values().cloned().collect::<Vec<_>>();
-            for handler in handlers {
-                let ctx = ctx.clone();
-                let data = data.clone();
-                task_spawner_clone.spawn_task(handler(ctx, data));
+            // Wrap the handlers in the interceptors, the first one added being the outermost
+            let handle_event: EventChain = Box::new(move |payload| Box::pin(event_dispatch.run(payload)));
+            let event_chain =
+                event_interceptors
+                    .iter()
+                    .rev()
+                    .fold(handle_event, |next, event_interceptor| -> EventChain {
+                        let ctx = ctx.clone();
+                        let event = event.clone();
+                        let event_interceptor = event_interceptor.clone();
+                        Box::new(move |payload| {
+                            event_interceptor(ctx, event.to_string(), payload, WsIoEventNext::new(next))
+                        })
+                    });
+
+            // Failures of handlers were already replied to, only errors of interceptors are left to reply to
+            if let Err(err) = event_chain(payload).await
+                && err.downcast_ref::<EventHandlerError>().is_none()
+            {
+                let _ = ctx.send_error(&err, claim_ack_id(ack_id, &ack_claimed)).await;
+                ctx.report_event_error(&event, err).await;
             }
 
             Ok(())
         });
+
+        match dispatch_mode {
+            WsIoEventDispatchMode::Concurrent => task_spawner.spawn_task(event_job),
+            _ => self.enqueue_event_job(event_job_key, event_job, task_spawner),
+        }
+
+        Ok(())
+    }
+
+    /// Whether reading is paused because as many events as allowed wait for their handlers to run, see
+    /// [`WsIoEventOverflowPolicy::Backpressure`].
+    #[inline]
+    pub fn is_reading_paused(&self) -> bool {
+        self.paused_reads.load(Ordering::Acquire) != 0
     }
 
+    /// Removes all handlers of `event`, including the pattern route registered with the same pattern.
     #[inline]
     pub fn off(&self, event: &str) {
         self.event_entries.write().remove(event);
+        self.pattern_entries.write().remove(event);
+    }
+
+    #[inline]
+    pub fn off_any(&self) {
+        self.any_handlers.write().clear();
     }
 
     #[inline]
-    pub fn off_by_handler_id(&self, event: &str, handler_id: u32) {
-        if let Some(event_entry) = self.event_entries.write().get(event).cloned() {
+    pub fn off_any_by_handler_id(&self, handler_id: u64) {
+        self.any_handlers.write().remove(&handler_id);
+    }
+
+    #[inline]
+    pub fn off_by_handler_id(&self, event: &str, handler_id: u64) {
+        for event_entries in [&self.event_entries, &self.pattern_entries] {
+            let Some(event_entry) = event_entries.read().get(event).cloned() else {
+                continue;
+            };
+
             event_entry.handlers.write().remove(&handler_id);
-            if event_entry.handlers.read().is_empty() {
-                self.event_entries.write().remove(event);
-            }
+            self.remove_event_entry_if_empty(event, &event_entry);
         }
     }
 
     #[inline]
-    pub fn on<H, Fut, D>(&self, event: &str, handler: H) -> u32
+    pub fn on<H, Fut, D, R>(&self, event: &str, handler: H) -> WsIoSubscription
     where
         H: Fn(Arc<C>, Arc<D>) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<()>> + Send + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
         D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
     {
-        let data_type_id = TypeId::of::<D>();
-
-        let mut event_entries = self.event_entries.write();
-        let event_entry = match event_entries.entry(event.into()) {
-            Entry::Occupied(occupied) => {
-                let event_entry = occupied.into_mut();
-                assert_eq!(
-                    event_entry.data_type_id, data_type_id,
-                    "Event '{}' already registered with a different data type — each event name must correspond to exactly one payload type.",
-                    event
-                );
-
-                event_entry
-            }
-            Entry::Vacant(vacant) => vacant.insert(Arc::new(EventEntry {
-                data_decoder: decode_data_as_any_arc::<D>,
-                data_type_id,
-                handlers: RwLock::new(FxHashMap::default()),
-            })),
-        };
+        self.insert_handler(
+            event,
+            None,
+            HandlerEntry::new::<D>(erase_handler(move |ctx, data, _| handler(ctx, data)), false, None),
+        )
+    }
 
+    /// Registers a catch-all handler that receives every event with its name and raw payload, whether or not other
+    /// handlers are registered for it.
+    #[inline]
+    pub fn on_any<H, Fut>(&self, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<C>, String, Option<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
         let handler_id = self.next_handler_id.fetch_add(1, Ordering::Relaxed);
-        event_entry.handlers.write().insert(
+        self.any_handlers.write().insert(
             handler_id,
-            Arc::new(move |connection, data| {
-                if (*data).type_id() != data_type_id {
-                    return Box::pin(async { Ok(()) });
-                }
-
-                Box::pin(handler(connection, data.downcast().unwrap()))
-            }),
+            Arc::new(move |ctx, event, data| Box::pin(handler(ctx, event, data))),
         );
 
-        handler_id
+        let any_handlers = Arc::downgrade(&self.any_handlers);
+        WsIoSubscription::new(handler_id, move || {
+            if let Some(any_handlers) = any_handlers.upgrade() {
+                any_handlers.write().remove(&handler_id);
+            }
+        })
+    }
+
+    /// Registers a handler for every event matching `pattern`, such as `chat:{room}:message` or `orders.*`.
+    ///
+    /// `{name}` captures one or more characters other than `.`, `:` and `/`, while `*` captures any non-empty text;
+    /// the captures are passed to the handler.
+    ///
+    /// Fails if `pattern` is malformed.
+    #[inline]
+    pub fn on_pattern<H, Fut, D, R>(&self, pattern: &str, handler: H) -> Result<WsIoSubscription>
+    where
+        H: Fn(Arc<C>, Arc<D>, WsIoEventParams) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        Ok(self.insert_handler(
+            pattern,
+            Some(EventPattern::parse(pattern)?),
+            HandlerEntry::new::<D>(erase_handler(handler), false, None),
+        ))
+    }
+
+    /// Registers a handler that receives the payload of `event` undecoded, along with the codec it was encoded with.
+    ///
+    /// The handler replies to acks with data that it must encode itself with the given codec.
+    #[inline]
+    pub fn on_raw<H, Fut>(&self, event: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<C>, String, Option<Bytes>, WsIoPacketCodec) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<Vec<u8>>>> + Send + 'static,
+    {
+        let event_name = event.to_owned();
+        let handler: Handler<C> = Arc::new(move |ctx, data, _, response_packet_codec| {
+            let Ok(raw_event_data) = data.downcast::<RawEventData>() else {
+                return Box::pin(async { bail!("Event data does not match the data type of the handler") });
+            };
+
+            let future = handler(
+                ctx,
+                event_name.clone(),
+                raw_event_data.data.clone(),
+                raw_event_data.packet_codec.clone(),
+            );
+
+            Box::pin(async move { Ok(response_packet_codec.and(future.await?)) })
+        });
+
+        self.insert_handler(
+            event,
+            None,
+            HandlerEntry {
+                data_decoder: decode_raw_event_data,
+                data_type_id: TypeId::of::<RawEventData>(),
+                handler,
+                once: false,
+                timeout: None,
+            },
+        )
+    }
+
+    /// Registers a handler that is removed after handling the next event.
+    #[inline]
+    pub fn once<H, Fut, D, R>(&self, event: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<C>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.insert_handler(
+            event,
+            None,
+            HandlerEntry::new::<D>(erase_handler(move |ctx, data, _| handler(ctx, data)), true, None),
+        )
+    }
+
+    /// Registers a handler that receives the binary attachments of `event` next to its decoded payload.
+    #[inline]
+    pub fn on_with_attachments<H, Fut, D, R>(&self, event: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<C>, Arc<D>, Vec<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        let handler: Handler<C> = Arc::new(move |ctx, data, _, response_packet_codec| {
+            let Ok(attached_event_data) = data.downcast::<AttachedEventData<D>>() else {
+                return Box::pin(async { bail!("Event data does not match the data type of the handler") });
+            };
+
+            let future = handler(
+                ctx,
+                attached_event_data.data.clone(),
+                attached_event_data.attachments.clone(),
+            );
+
+            Box::pin(async move {
+                let response = future.await?;
+                response_packet_codec
+                    .map(|packet_codec| packet_codec.encode_data(&response))
+                    .transpose()
+            })
+        });
+
+        self.insert_handler(
+            event,
+            None,
+            HandlerEntry {
+                data_decoder: decode_attached_data_as_any_arc::<D>,
+                data_type_id: TypeId::of::<AttachedEventData<D>>(),
+                handler,
+                once: false,
+                timeout: None,
+            },
+        )
+    }
+
+    /// Registers a handler that is cancelled and reported as failed when it runs longer than `timeout`, overriding
+    /// the default handler timeout.
+    #[inline]
+    pub fn on_with_timeout<H, Fut, D, R>(&self, event: &str, timeout: Duration, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<C>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.insert_handler(
+            event,
+            None,
+            HandlerEntry::new::<D>(
+                erase_handler(move |ctx, data, _| handler(ctx, data)),
+                false,
+                Some(timeout),
+            ),
+        )
+    }
+
+    /// Number of times reading was paused so far, telling whether it was paused in the meantime.
+    #[inline]
+    pub fn read_pauses(&self) -> u64 {
+        self.read_pauses.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the next `event` and returns its data.
+    ///
+    /// The handler is registered before this returns, so events received before the future is first polled are
+    /// not missed, and it is removed if the future is dropped or times out.
+    pub fn wait_for<D: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        event: &str,
+        duration: Duration,
+    ) -> impl Future<Output = Result<Arc<D>>> + Send + use<C, S, D> {
+        let (data_tx, data_rx) = channel();
+        let data_tx = Mutex::new(Some(data_tx));
+        let subscription = self.once(event, move |_, data: Arc<D>| {
+            if let Some(data_tx) = data_tx.lock().take() {
+                let _ = data_tx.send(data);
+            }
+
+            async { Ok(()) }
+        });
+
+        let event = event.to_owned();
+        async move {
+            let _subscription = subscription;
+            match timeout(duration, data_rx).await {
+                Ok(Ok(data)) => Ok(data),
+                Ok(Err(_)) => bail!("Handler for event '{event}' dropped before the event was received"),
+                Err(_) => bail!("Event '{event}' not received within {duration:?}"),
+            }
+        }
     }
 }
 
@@ -163,10 +920,839 @@ impl<C: Send + Sync + 'static, S: TaskSpawner> WsIoEventRegistry<C, S> {
 static EMPTY_EVENT_DATA_ANY_ARC: LazyLock<Arc<dyn Any + Send + Sync>> = LazyLock::new(|| Arc::new(()));
 
 // Functions
+#[inline]
+fn decode_attached_data_as_any_arc<D: DeserializeOwned + Send + Sync + 'static>(
+    payload: &WsIoEventPayload,
+    packet_codec: &WsIoPacketCodec,
+) -> Result<Arc<dyn Any + Send + Sync>> {
+    // Leave the empty marker as is when data is missing, so only handlers expecting no data receive the attachments
+    Ok(
+        match decode_data_as_any_arc::<D>(payload, packet_codec)?.downcast::<D>() {
+            Ok(data) => Arc::new(AttachedEventData {
+                attachments: payload.attachments.clone(),
+                data,
+            }),
+            Err(data) => data,
+        },
+    )
+}
+
 #[inline]
 fn decode_data_as_any_arc<D: DeserializeOwned + Send + Sync + 'static>(
-    bytes: &[u8],
+    payload: &WsIoEventPayload,
+    packet_codec: &WsIoPacketCodec,
+) -> Result<Arc<dyn Any + Send + Sync>> {
+    Ok(match &payload.data {
+        Some(bytes) => Arc::new(packet_codec.decode_data::<D>(bytes)?),
+        None => EMPTY_EVENT_DATA_ANY_ARC.clone(),
+    })
+}
+
+#[inline]
+fn decode_raw_event_data(
+    payload: &WsIoEventPayload,
     packet_codec: &WsIoPacketCodec,
 ) -> Result<Arc<dyn Any + Send + Sync>> {
-    Ok(Arc::new(packet_codec.decode_data::<D>(bytes)?))
+    Ok(Arc::new(RawEventData {
+        data: payload.data.clone(),
+        packet_codec: packet_codec.clone(),
+    }))
+}
+
+#[inline]
+fn remove_event_entry_if_empty<C>(
+    event_entries: &RwLock<FxHashMap<String, Arc<EventEntry<C>>>>,
+    key: &str,
+    event_entry: &Arc<EventEntry<C>>,
+) {
+    // Check under the entries lock so a handler registered concurrently through `on` is never dropped
+    let mut event_entries = event_entries.write();
+    if event_entries
+        .get(key)
+        .is_some_and(|current_event_entry| Arc::ptr_eq(current_event_entry, event_entry))
+        && event_entry.handlers.read().is_empty()
+    {
+        event_entries.remove(key);
+    }
+}
+
+/// Claims the handlers of the route `key` that should receive an event, removing the one-shot ones from it.
+fn claim_handlers<C>(
+    event_entries: &RwLock<FxHashMap<String, Arc<EventEntry<C>>>>,
+    key: &str,
+    event_entry: &Arc<EventEntry<C>>,
+    has_data: bool,
+) -> Vec<(u64, HandlerEntry<C>)> {
+    let mut handlers = Vec::new();
+    let mut once_handler_ids = Vec::new();
+    for (handler_id, handler_entry) in event_entry.handlers.read().iter() {
+        match handler_entry.once {
+            true => {
+                // Skip packets without data that the handler would ignore anyway
+                if has_data || handler_entry.accepts_missing_data() {
+                    once_handler_ids.push(*handler_id);
+                }
+            }
+            false => handlers.push((*handler_id, handler_entry.clone())),
+        }
+    }
+
+    // Claim one-shot handlers under the write lock so that concurrent events deliver to each exactly once
+    if !once_handler_ids.is_empty() {
+        let mut event_handlers = event_entry.handlers.write();
+        handlers.extend(
+            once_handler_ids
+                .iter()
+                .filter_map(|handler_id| Some((*handler_id, event_handlers.remove(handler_id)?))),
+        );
+
+        let is_empty = event_handlers.is_empty();
+        drop(event_handlers);
+        if is_empty {
+            remove_event_entry_if_empty(event_entries, key, event_entry);
+        }
+    }
+
+    handlers
+}
+
+/// Claims the ack of an event for the calling reply, returning `None` once another reply has claimed it.
+#[inline]
+fn claim_ack_id(ack_id: Option<u32>, ack_claimed: &AtomicBool) -> Option<u32> {
+    ack_id.filter(|_| !ack_claimed.swap(true, Ordering::AcqRel))
+}
+
+#[inline]
+fn erase_handler<C, H, Fut, D, R>(handler: H) -> Handler<C>
+where
+    C: Send + Sync + 'static,
+    H: Fn(Arc<C>, Arc<D>, WsIoEventParams) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<R>> + Send + 'static,
+    D: DeserializeOwned + Send + Sync + 'static,
+    R: Serialize + Send + 'static,
+{
+    let data_type_id = TypeId::of::<D>();
+    Arc::new(move |ctx, data, params, response_packet_codec| {
+        if (*data).type_id() != data_type_id {
+            return Box::pin(async { bail!("Event data does not match the data type of the handler") });
+        }
+
+        let future = handler(ctx, data.downcast().unwrap(), params);
+        Box::pin(async move {
+            let response = future.await?;
+            response_packet_codec
+                .map(|packet_codec| packet_codec.encode_data(&response))
+                .transpose()
+        })
+    })
+}
+
+async fn with_handler_timeout<T>(
+    future: impl Future<Output = Result<T>>,
+    event: &str,
+    duration: Option<Duration>,
+) -> Result<T> {
+    let Some(duration) = duration else {
+        return future.await;
+    };
+
+    // Keep the elapsed error in the chain so the peer is told the handler timed out
+    timeout(duration, future).await.map_err(|err| {
+        anyhow::Error::new(err).context(format!("Handler for event '{event}' timed out after {duration:?}"))
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+
+    use tokio::{
+        task::yield_now,
+        time::sleep,
+    };
+
+    use super::*;
+    use crate::{
+        packet::{
+            WsIoPacket,
+            WsIoPacketType,
+        },
+        testing::{
+            TestPeer,
+            is_pending,
+        },
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn cancels_handlers_running_past_their_timeout() {
+        /// Flags the handler future as cancelled when it is dropped before completing.
+        struct Cancelled(Arc<AtomicBool>);
+
+        impl Drop for Cancelled {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::Release);
+            }
+        }
+
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Concurrent,
+            Some(Duration::from_secs(1)),
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let handler_cancelled = cancelled.clone();
+        event_registry
+            .on("stuck", move |_, _: Arc<()>| {
+                let cancelled = Cancelled(handler_cancelled.clone());
+                async move {
+                    let _cancelled = cancelled;
+                    pending::<Result<()>>().await
+                }
+            })
+            .detach();
+
+        // Handlers registered with their own timeout may outlive the default one
+        event_registry
+            .on_with_timeout("slow", Duration::from_secs(10), |_, _: Arc<()>| async {
+                sleep(Duration::from_secs(5)).await;
+                Ok(5)
+            })
+            .detach();
+
+        peer.dispatch(&event_registry, "stuck", None, Some(1)).await.unwrap();
+        peer.dispatch(&event_registry, "slow", None, Some(2)).await.unwrap();
+        peer.wait_for_idle().await;
+        assert!(cancelled.load(Ordering::Acquire));
+
+        let replies = peer
+            .take_sent_packets()
+            .into_iter()
+            .map(|sent_packet| {
+                (
+                    sent_packet.ack_id,
+                    sent_packet.r#type,
+                    sent_packet.data,
+                    sent_packet.message,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            replies,
+            [
+                (
+                    Some(1),
+                    WsIoPacketType::Error,
+                    None,
+                    Some("Handler for event 'stuck' timed out after 1s: deadline has elapsed".into())
+                ),
+                (Some(2), WsIoPacketType::Ack, Some(b"5".to_vec()), None),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn delivers_one_shot_handlers_once_under_concurrent_events() {
+        const EVENT_DATA: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Concurrent,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+
+        for _ in 0..16 {
+            let once_calls = Arc::new(AtomicUsize::new(0));
+            let handler_once_calls = once_calls.clone();
+            event_registry
+                .once("event", move |_, _: Arc<u32>| {
+                    handler_once_calls.fetch_add(1, Ordering::AcqRel);
+                    async { Ok(()) }
+                })
+                .detach();
+
+            let data = event_registry.wait_for::<u32>("event", Duration::from_secs(5));
+            for data in EVENT_DATA {
+                peer.dispatch(&event_registry, "event", Some(data), None).await.unwrap();
+            }
+
+            assert!(*data.await.unwrap() < EVENT_DATA.len() as u32);
+            peer.wait_for_idle().await;
+            assert_eq!(once_calls.load(Ordering::Acquire), 1);
+            assert!(event_registry.event_entries.read().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn dropping_subscriptions_unregisters_handlers() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler_calls = calls.clone();
+        let subscription = event_registry.on("event", move |_, _: Arc<u32>| {
+            handler_calls.fetch_add(1, Ordering::AcqRel);
+            async { Ok(()) }
+        });
+
+        let any_calls = calls.clone();
+        let any_subscription = event_registry.on_any(move |_, _, _| {
+            any_calls.fetch_add(1, Ordering::AcqRel);
+            async { Ok(()) }
+        });
+
+        peer.dispatch(&event_registry, "event", Some("7"), None).await.unwrap();
+        peer.wait_for_idle().await;
+        assert_eq!(calls.load(Ordering::Acquire), 2);
+
+        drop(subscription);
+        drop(any_subscription);
+        assert!(event_registry.any_handlers.read().is_empty());
+        assert!(event_registry.event_entries.read().is_empty());
+
+        // Dropping a pending wait_for removes its handler too
+        drop(event_registry.wait_for::<u32>("event", Duration::from_secs(1)));
+        assert!(event_registry.event_entries.read().is_empty());
+
+        peer.dispatch(&event_registry, "event", Some("7"), None).await.unwrap();
+        peer.wait_for_idle().await;
+        assert_eq!(calls.load(Ordering::Acquire), 2);
+    }
+
+    #[tokio::test]
+    async fn interceptors_drop_or_reject_events() {
+        let peer = TestPeer::new();
+        let event_interceptor: WsIoEventInterceptor<TestPeer> = Arc::new(|_, event, payload, next| {
+            Box::pin(async move {
+                match event.as_str() {
+                    "drop" => Ok(()),
+                    "reject" => bail!("Rejected"),
+                    _ => next.run(payload).await,
+                }
+            })
+        });
+
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Sequential,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            vec![event_interceptor],
+        );
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for event in ["drop", "pass", "reject"] {
+            let calls = calls.clone();
+            event_registry
+                .once(event, move |_, _: Arc<u32>| {
+                    calls.lock().push(event);
+                    async { Ok(()) }
+                })
+                .detach();
+        }
+
+        for (ack_id, event) in ["drop", "reject", "pass", "drop"].into_iter().enumerate() {
+            peer.dispatch(&event_registry, event, Some("7"), Some(ack_id as u32))
+                .await
+                .unwrap();
+        }
+
+        peer.wait_for_idle().await;
+        assert_eq!(*calls.lock(), ["pass"]);
+
+        // Dropped events leave one-shot handlers registered
+        let replies = peer
+            .take_sent_packets()
+            .into_iter()
+            .map(|sent_packet| (sent_packet.ack_id, sent_packet.r#type, sent_packet.message))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            replies,
+            [
+                (Some(1), WsIoPacketType::Error, Some("Rejected".into())),
+                (Some(2), WsIoPacketType::Ack, None),
+            ]
+        );
+        assert!(event_registry.event_entries.read().contains_key("drop"));
+    }
+
+    #[tokio::test]
+    async fn interceptors_wrap_handlers_in_order() {
+        let peer = TestPeer::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let outer_calls = calls.clone();
+        let outer_event_interceptor: WsIoEventInterceptor<TestPeer> = Arc::new(move |_, _, payload, next| {
+            let calls = outer_calls.clone();
+            Box::pin(async move {
+                calls.lock().push("outer".to_owned());
+                let result = next.run(payload).await;
+                calls.lock().push(format!("outer saw {}", result.as_ref().unwrap_err()));
+                result
+            })
+        });
+
+        let inner_calls = calls.clone();
+        let inner_event_interceptor: WsIoEventInterceptor<TestPeer> = Arc::new(move |_, _, payload, next| {
+            let calls = inner_calls.clone();
+            Box::pin(async move {
+                calls
+                    .lock()
+                    .push(format!("inner saw {} attachments", payload.attachments.len()));
+
+                next.run(WsIoEventPayload {
+                    data: Some(Bytes::from_static(b"8")),
+                    ..payload
+                })
+                .await
+            })
+        });
+
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Concurrent,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            vec![outer_event_interceptor, inner_event_interceptor],
+        );
+
+        let handler_calls = calls.clone();
+        event_registry
+            .on("event", move |_, data: Arc<u32>| {
+                handler_calls.lock().push(format!("handler got {data}"));
+                async { bail!("Handler failed") as Result<()> }
+            })
+            .detach();
+
+        let payload = WsIoEventPayload {
+            attachments: vec![Bytes::from_static(b"attachment")],
+            data: Some(Bytes::from_static(b"7")),
+        };
+
+        event_registry
+            .dispatch_event_packet(
+                peer.clone(),
+                "event",
+                &WsIoPacketCodec::SerdeJson,
+                payload,
+                Some(1),
+                &peer,
+            )
+            .await
+            .unwrap();
+
+        peer.wait_for_idle().await;
+        assert_eq!(
+            *calls.lock(),
+            [
+                "outer",
+                "inner saw 1 attachments",
+                "handler got 8",
+                "outer saw Handler failed"
+            ]
+        );
+
+        // The failure passed up the chain is only replied to once
+        let sent_packets = peer.take_sent_packets();
+        assert_eq!(sent_packets.len(), 1);
+        assert_eq!(
+            (sent_packets[0].ack_id, sent_packets[0].message.as_deref()),
+            (Some(1), Some("Handler failed"))
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_in_flight_events_with_backpressure() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Concurrent,
+            None,
+            Some(2),
+            WsIoEventOverflowPolicy::Backpressure,
+            Vec::new(),
+        );
+
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Semaphore::new(0));
+        let running = Arc::new(AtomicUsize::new(0));
+        let started = Arc::new(Semaphore::new(0));
+        let handler_max_running = max_running.clone();
+        let handler_release = release.clone();
+        let handler_started = started.clone();
+        event_registry
+            .on("event", move |_, _: Arc<()>| {
+                let max_running = handler_max_running.clone();
+                let release = handler_release.clone();
+                let running = running.clone();
+                let started = handler_started.clone();
+                async move {
+                    max_running.fetch_max(running.fetch_add(1, Ordering::AcqRel) + 1, Ordering::AcqRel);
+                    started.add_permits(1);
+                    release.acquire().await?.forget();
+                    running.fetch_sub(1, Ordering::AcqRel);
+                    Ok(())
+                }
+            })
+            .detach();
+
+        // Two events run and two wait for them, so reading the next one pauses
+        for _ in 0..4 {
+            peer.dispatch(&event_registry, "event", None, None).await.unwrap();
+        }
+
+        started.acquire_many(2).await.unwrap().forget();
+        assert!(is_pending(peer.dispatch(&event_registry, "event", None, None)));
+        assert_eq!(event_registry.read_pauses(), 1);
+
+        release.add_permits(4);
+        peer.wait_for_idle().await;
+        assert_eq!(max_running.load(Ordering::Acquire), 2);
+        assert_eq!(started.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn limits_in_flight_events_with_disconnect() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Concurrent,
+            None,
+            Some(1),
+            WsIoEventOverflowPolicy::Disconnect,
+            Vec::new(),
+        );
+
+        let release = Arc::new(Semaphore::new(0));
+        let handler_release = release.clone();
+        event_registry
+            .on("event", move |_, _: Arc<()>| {
+                let release = handler_release.clone();
+                async move {
+                    release.acquire().await?.forget();
+                    Ok(())
+                }
+            })
+            .detach();
+
+        peer.dispatch(&event_registry, "event", None, None).await.unwrap();
+        let err = peer.dispatch(&event_registry, "event", None, None).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Too many in-flight events, received 'event'");
+        assert!(!event_registry.is_reading_paused());
+
+        // The permit is released once the handler completes
+        release.add_permits(1);
+        peer.wait_for_idle().await;
+        peer.dispatch(&event_registry, "event", None, None).await.unwrap();
+        release.add_permits(1);
+        peer.wait_for_idle().await;
+    }
+
+    #[tokio::test]
+    async fn orders_events_per_name_when_sequential_per_event() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::SequentialPerEvent,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+
+        let release = Arc::new(Semaphore::new(0));
+        let blocked_release = release.clone();
+        event_registry
+            .on("blocked", move |_, _: Arc<()>| {
+                let release = blocked_release.clone();
+                async move {
+                    release.acquire().await?.forget();
+                    Ok(())
+                }
+            })
+            .detach();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let handled = Arc::new(Semaphore::new(0));
+        let event_calls = calls.clone();
+        let event_handled = handled.clone();
+        event_registry
+            .on("event", move |_, data: Arc<u32>| {
+                event_calls.lock().push(*data);
+                event_handled.add_permits(1);
+                async { Ok(()) }
+            })
+            .detach();
+
+        // A blocked event only holds back the events of the same name
+        for (event, data) in [
+            ("blocked", None),
+            ("blocked", None),
+            ("event", Some("1")),
+            ("event", Some("2")),
+        ] {
+            peer.dispatch(&event_registry, event, data, None).await.unwrap();
+        }
+
+        handled.acquire_many(2).await.unwrap().forget();
+        assert_eq!(*calls.lock(), [1, 2]);
+
+        release.add_permits(2);
+        peer.wait_for_idle().await;
+    }
+
+    #[tokio::test]
+    async fn passes_attachments_through_text_codecs() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::default();
+        event_registry
+            .on_with_attachments("event", |_, data: Arc<String>, attachments| async move {
+                Ok((
+                    data.to_string(),
+                    attachments
+                        .iter()
+                        .map(|attachment| attachment.to_vec())
+                        .collect::<Vec<_>>(),
+                ))
+            })
+            .detach();
+
+        let packet_codec = WsIoPacketCodec::SerdeJson;
+        let packet = WsIoPacket::new_event_with_attachments(
+            "event",
+            Some(packet_codec.encode_data(&"text").unwrap()),
+            vec![b"\x00\xff", b""],
+        );
+
+        let frame = Bytes::from(packet_codec.encode(&packet).unwrap());
+        let mut decoded = packet_codec
+            .decode_frame(&frame, !packet_codec.is_text_frame(&packet))
+            .unwrap();
+
+        let payload = WsIoEventPayload {
+            attachments: decoded.take_frame_attachments(&frame),
+            data: decoded.take_frame_data(&frame),
+        };
+
+        event_registry
+            .dispatch_event_packet(
+                peer.clone(),
+                decoded.key.as_deref().unwrap(),
+                &packet_codec,
+                payload,
+                Some(1),
+                &peer,
+            )
+            .await
+            .unwrap();
+
+        peer.wait_for_idle().await;
+        let sent_packets = peer.take_sent_packets();
+        assert_eq!(sent_packets.len(), 1);
+        assert_eq!(
+            packet_codec
+                .decode_data::<(String, Vec<Vec<u8>>)>(sent_packets[0].data.as_deref().unwrap())
+                .unwrap(),
+            ("text".to_owned(), vec![vec![0x00, 0xff], Vec::new()])
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_once_per_event() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::default();
+        event_registry
+            .on("event", |_, data: Arc<u32>| async move { Ok(*data + 1) })
+            .detach();
+        event_registry
+            .on("event", |_, data: Arc<u32>| async move { Ok(*data + 2) })
+            .detach();
+
+        peer.dispatch(&event_registry, "event", Some("7"), Some(1))
+            .await
+            .unwrap();
+        peer.wait_for_idle().await;
+
+        let sent_packets = peer.take_sent_packets();
+        assert_eq!(sent_packets.len(), 1);
+        assert_eq!(
+            (sent_packets[0].r#type, sent_packets[0].ack_id),
+            (WsIoPacketType::Ack, Some(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_once_per_event_when_handlers_fail() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Sequential,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+        event_registry
+            .on("event", |_, _: Arc<u32>| async move {
+                bail!("Handler failed") as Result<()>
+            })
+            .detach();
+        event_registry
+            .on("event", |_, data: Arc<u32>| async move { Ok(*data) })
+            .detach();
+        event_registry
+            .on("event", |_, _: Arc<String>| async move { Ok(()) })
+            .detach();
+
+        peer.dispatch(&event_registry, "event", Some("7"), Some(1))
+            .await
+            .unwrap();
+        peer.wait_for_idle().await;
+
+        let replies = peer
+            .take_sent_packets()
+            .into_iter()
+            .map(|sent_packet| (sent_packet.ack_id, sent_packet.r#type, sent_packet.message.unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(replies, [(Some(1), WsIoPacketType::Error, "Handler failed".into())]);
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_data_only_when_no_handler_decodes_it() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Sequential,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+        event_registry
+            .on("event", |_, _: Arc<String>| async move { Ok(()) })
+            .detach();
+        event_registry
+            .on("event", |_, _: Arc<Vec<u8>>| async move { Ok(()) })
+            .detach();
+
+        peer.dispatch(&event_registry, "event", Some("7"), Some(1))
+            .await
+            .unwrap();
+        peer.wait_for_idle().await;
+
+        let sent_packets = peer.take_sent_packets();
+        assert_eq!(sent_packets.len(), 1);
+        assert_eq!(
+            (sent_packets[0].ack_id, sent_packets[0].message.as_deref()),
+            (Some(1), Some("Failed to decode event data (invalid_data)"))
+        );
+
+        event_registry
+            .on("event", |_, data: Arc<u32>| async move { Ok(*data) })
+            .detach();
+
+        peer.dispatch(&event_registry, "event", Some("7"), Some(2))
+            .await
+            .unwrap();
+        peer.wait_for_idle().await;
+
+        let sent_packets = peer.take_sent_packets();
+        assert_eq!(sent_packets.len(), 1);
+        assert_eq!(
+            (
+                sent_packets[0].r#type,
+                sent_packets[0].ack_id,
+                sent_packets[0].data.as_deref()
+            ),
+            (WsIoPacketType::Ack, Some(2), Some(&b"7"[..]))
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_events_one_at_a_time_when_sequential() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Sequential,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for event in ["first", "second"] {
+            let calls = calls.clone();
+            event_registry
+                .on(event, move |_, data: Arc<u32>| {
+                    let calls = calls.clone();
+                    async move {
+                        calls.lock().push(format!("start {event} {data}"));
+
+                        // Give the other events a chance to start if they were not queued behind this one
+                        for _ in 0..4 {
+                            yield_now().await;
+                        }
+
+                        calls.lock().push(format!("end {event} {data}"));
+                        Ok(())
+                    }
+                })
+                .detach();
+        }
+
+        for (event, data) in [("first", "1"), ("second", "2"), ("first", "3")] {
+            peer.dispatch(&event_registry, event, Some(data), None).await.unwrap();
+        }
+
+        peer.wait_for_idle().await;
+        assert_eq!(
+            *calls.lock(),
+            [
+                "start first 1",
+                "end first 1",
+                "start second 2",
+                "end second 2",
+                "start first 3",
+                "end first 3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_handlers_in_registration_order_across_routes() {
+        let peer = TestPeer::new();
+        let event_registry = WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Sequential,
+            None,
+            None,
+            WsIoEventOverflowPolicy::default(),
+            Vec::new(),
+        );
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for index in 0..8 {
+            let calls = calls.clone();
+            let handler = move |_, _: Arc<u32>| {
+                calls.lock().push(index);
+                async { Ok(()) }
+            };
+
+            let subscription = match index % 3 {
+                0 => event_registry.on("event", handler),
+                1 => event_registry
+                    .on_pattern("ev*", move |ctx, data, _| handler(ctx, data))
+                    .unwrap(),
+                _ => event_registry.once("event", handler),
+            };
+
+            subscription.detach();
+        }
+
+        peer.dispatch(&event_registry, "event", Some("7"), None).await.unwrap();
+        peer.wait_for_idle().await;
+        assert_eq!(*calls.lock(), [0, 1, 2, 3, 4, 5, 6, 7]);
+    }
 }
@@ -0,0 +1,47 @@
+// Types
+type Unsubscribe = Box<dyn FnOnce() + Send + Sync + 'static>;
+
+// Structs
+/// Registered event handler that is removed when this subscription is dropped.
+///
+/// Call [`detach`](Self::detach) to keep the handler registered for as long as its connection lives.
+#[must_use = "the handler is removed as soon as the subscription is dropped; call `detach` to keep it registered"]
+pub struct WsIoSubscription {
+    handler_id: u64,
+    unsubscribe: Option<Unsubscribe>,
+}
+
+impl Drop for WsIoSubscription {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+    }
+}
+
+impl WsIoSubscription {
+    #[inline]
+    pub(crate) fn new(handler_id: u64, unsubscribe: impl FnOnce() + Send + Sync + 'static) -> Self {
+        Self {
+            handler_id,
+            unsubscribe: Some(Box::new(unsubscribe)),
+        }
+    }
+
+    // Public methods
+    /// Keeps the handler registered and returns its id, which can still be passed to `off_by_handler_id`.
+    #[inline]
+    pub fn detach(mut self) -> u64 {
+        self.unsubscribe = None;
+        self.handler_id
+    }
+
+    #[inline]
+    pub fn handler_id(&self) -> u64 {
+        self.handler_id
+    }
+
+    /// Removes the handler now, like dropping the subscription.
+    #[inline]
+    pub fn unsubscribe(self) {}
+}
@@ -1,8 +1,15 @@
 use tungstenite::protocol::WebSocketConfig;
 
+pub mod ack;
 pub mod atomic;
+pub mod error;
 pub mod event;
+pub mod outbound;
 pub mod packet;
+pub mod protocol;
+pub mod stream;
+#[cfg(test)]
+mod testing;
 pub mod traits;
 pub mod types;
 pub mod utils;
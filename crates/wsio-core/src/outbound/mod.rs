@@ -0,0 +1,3 @@
+pub mod overflow;
+pub mod priority;
+pub mod queue;
@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+// Enums
+/// Action taken when a message is sent while the outbound queue of a peer is full.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WsIoOutboundOverflowPolicy {
+    /// Wait until the peer catches up and there is room in the queue.
+    #[default]
+    Block,
+
+    /// Wait until there is room in the queue, dropping the message and failing the send if none frees up in time.
+    BlockWithTimeout(Duration),
+
+    /// Close the connection with the peer, dropping every queued message.
+    Disconnect,
+
+    /// Drop the message being sent, keeping the queued ones.
+    DropNewest,
+
+    /// Drop the oldest queued message to make room for the one being sent.
+    DropOldest,
+}
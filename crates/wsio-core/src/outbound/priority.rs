@@ -0,0 +1,30 @@
+use crate::packet::WsIoPacketType;
+
+// Enums
+/// Lane of the outbound queue a message waits in, the high priority lane being drained first.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WsIoOutboundPriority {
+    /// Lane of control packets such as acks, errors and heartbeats, for messages that must not wait behind bulk data.
+    High,
+
+    #[default]
+    Normal,
+}
+
+impl WsIoOutboundPriority {
+    /// Priority of packets of type `packet_type`, control packets going through the high priority lane.
+    #[inline]
+    pub fn for_packet_type(packet_type: WsIoPacketType) -> Self {
+        match packet_type {
+            // Stream chunks stay in the same lane as the packets opening and ending their stream to keep their order,
+            // and disconnections in the same lane as events so that those emitted before are delivered first
+            WsIoPacketType::Disconnect
+            | WsIoPacketType::Event
+            | WsIoPacketType::StreamData
+            | WsIoPacketType::StreamEnd
+            | WsIoPacketType::StreamOpen
+            | WsIoPacketType::Unknown(_) => Self::Normal,
+            _ => Self::High,
+        }
+    }
+}
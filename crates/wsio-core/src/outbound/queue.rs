@@ -0,0 +1,561 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{
+    Result,
+    bail,
+};
+use parking_lot::Mutex;
+use tokio::{
+    pin,
+    sync::Notify,
+    time::timeout,
+};
+use tungstenite::Message;
+
+use super::priority::WsIoOutboundPriority;
+use crate::{
+    packet::codecs::WsIoPacketCodec,
+    types::hashers::FxHashMap,
+};
+
+// Structs
+struct QueueLane {
+    capacity: usize,
+
+    /// Sequence number of each queued message carrying a coalescing key, by key.
+    coalescing_keys: FxHashMap<Arc<str>, u64>,
+
+    /// Sequence number of the message at the front of the queue, the others following without gaps.
+    front_sequence: u64,
+
+    messages: VecDeque<WsIoOutboundMessage>,
+}
+
+impl QueueLane {
+    #[inline]
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            coalescing_keys: FxHashMap::default(),
+            front_sequence: 0,
+            messages: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    // Private methods
+    fn clear(&mut self) -> usize {
+        let cleared = self.messages.len();
+        self.coalescing_keys.clear();
+        self.front_sequence += cleared as u64;
+        self.messages.clear();
+        cleared
+    }
+
+    #[inline]
+    fn is_full(&self) -> bool {
+        self.messages.len() >= self.capacity
+    }
+
+    fn pop_front(&mut self) -> Option<WsIoOutboundMessage> {
+        let message = self.messages.pop_front()?;
+        if let Some(coalescing_key) = &message.coalescing_key
+            && self.coalescing_keys.get(coalescing_key) == Some(&self.front_sequence)
+        {
+            self.coalescing_keys.remove(coalescing_key);
+        }
+
+        self.front_sequence += 1;
+        Some(message)
+    }
+
+    fn push_back(&mut self, message: WsIoOutboundMessage) {
+        if let Some(coalescing_key) = &message.coalescing_key {
+            self.coalescing_keys
+                .insert(coalescing_key.clone(), self.front_sequence + self.messages.len() as u64);
+        }
+
+        self.messages.push_back(message);
+    }
+
+    /// Replaces the queued message with the same coalescing key as `message` in place, returning whether one was found.
+    fn try_coalesce(&mut self, message: &WsIoOutboundMessage) -> bool {
+        let Some(sequence) = message
+            .coalescing_key
+            .as_ref()
+            .and_then(|coalescing_key| self.coalescing_keys.get(coalescing_key))
+        else {
+            return false;
+        };
+
+        self.messages[(sequence - self.front_sequence) as usize] = message.clone();
+        true
+    }
+}
+
+struct QueueState {
+    closed: bool,
+
+    /// Lanes in order of priority, the first one being drained first.
+    lanes: [QueueLane; 2],
+}
+
+impl QueueState {
+    #[inline]
+    fn clear(&mut self) -> usize {
+        self.lanes.iter_mut().map(QueueLane::clear).sum()
+    }
+
+    #[inline]
+    fn lane(&mut self, priority: WsIoOutboundPriority) -> &mut QueueLane {
+        match priority {
+            WsIoOutboundPriority::High => &mut self.lanes[0],
+            WsIoOutboundPriority::Normal => &mut self.lanes[1],
+        }
+    }
+
+    #[inline]
+    fn pop_front(&mut self) -> Option<WsIoOutboundMessage> {
+        self.lanes.iter_mut().find_map(QueueLane::pop_front)
+    }
+}
+
+struct SharedQueue {
+    message_notify: Notify,
+    space_notify: Notify,
+    state: Mutex<QueueState>,
+}
+
+impl SharedQueue {
+    #[inline]
+    fn close(&self) {
+        self.state.lock().closed = true;
+        self.message_notify.notify_one();
+        self.space_notify.notify_waiters();
+    }
+}
+
+/// Message waiting in an outbound queue.
+#[derive(Clone)]
+pub struct WsIoOutboundMessage {
+    coalescing_key: Option<Arc<str>>,
+    message: Arc<Message>,
+
+    /// Packet codec the message was encoded with, when it may be sent after another codec was negotiated.
+    packet_codec: Option<WsIoPacketCodec>,
+
+    priority: WsIoOutboundPriority,
+}
+
+impl From<Arc<Message>> for WsIoOutboundMessage {
+    #[inline]
+    fn from(message: Arc<Message>) -> Self {
+        Self {
+            coalescing_key: None,
+            message,
+            packet_codec: None,
+            priority: WsIoOutboundPriority::default(),
+        }
+    }
+}
+
+impl WsIoOutboundMessage {
+    // Public methods
+    /// Tags `message` so that it replaces the queued message with the same `coalescing_key`, if any, instead of being
+    /// appended to the queue.
+    #[inline]
+    pub fn coalesced(message: Arc<Message>, coalescing_key: Arc<str>) -> Self {
+        Self {
+            coalescing_key: Some(coalescing_key),
+            message,
+            packet_codec: None,
+            priority: WsIoOutboundPriority::default(),
+        }
+    }
+
+    #[inline]
+    pub fn message(&self) -> &Arc<Message> {
+        &self.message
+    }
+
+    #[inline]
+    pub fn packet_codec(&self) -> Option<&WsIoPacketCodec> {
+        self.packet_codec.as_ref()
+    }
+
+    #[inline]
+    pub fn priority(&self) -> WsIoOutboundPriority {
+        self.priority
+    }
+
+    /// Tags the message with the packet codec it was encoded with, so that it can be discarded instead of sent to a
+    /// peer that negotiated another codec in the meantime.
+    #[inline]
+    pub fn with_packet_codec(mut self, packet_codec: WsIoPacketCodec) -> Self {
+        self.packet_codec = Some(packet_codec);
+        self
+    }
+
+    #[inline]
+    pub fn with_priority(mut self, priority: WsIoOutboundPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Sending half of the bounded queue of messages waiting to be written to a peer.
+///
+/// Messages wait in one lane per priority, the capacity of the queue being split between them: an eighth of it is
+/// reserved for the high priority lane, as control packets are few and small, and the rest goes to the normal one.
+/// Unlike a channel, queued messages can be discarded or replaced from the sending side, which overflow policies and
+/// coalescing rely on.
+pub struct WsIoOutboundQueue {
+    shared: Arc<SharedQueue>,
+}
+
+impl Drop for WsIoOutboundQueue {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+impl WsIoOutboundQueue {
+    #[inline]
+    pub fn new(capacity: usize) -> (Self, WsIoOutboundQueueReceiver) {
+        let high_priority_capacity = (capacity / 8).max(1);
+        let shared = Arc::new(SharedQueue {
+            message_notify: Notify::new(),
+            space_notify: Notify::new(),
+            state: Mutex::new(QueueState {
+                closed: false,
+                lanes: [
+                    QueueLane::new(high_priority_capacity),
+                    QueueLane::new(capacity.saturating_sub(high_priority_capacity).max(1)),
+                ],
+            }),
+        });
+
+        (Self { shared: shared.clone() }, WsIoOutboundQueueReceiver { shared })
+    }
+
+    // Private methods
+    async fn push_when_space(&self, message: WsIoOutboundMessage) -> Result<()> {
+        loop {
+            // Register interest before checking so that space freed in between is not missed
+            let space_notified = self.shared.space_notify.notified();
+            pin!(space_notified);
+            space_notified.as_mut().enable();
+            if self.try_push_inner(&message)? {
+                return Ok(());
+            }
+
+            space_notified.await;
+        }
+    }
+
+    fn try_push_inner(&self, message: &WsIoOutboundMessage) -> Result<bool> {
+        let mut state = self.shared.state.lock();
+        if state.closed {
+            bail!("Outbound queue closed");
+        }
+
+        let lane = state.lane(message.priority);
+        if !lane.try_coalesce(message) {
+            if lane.is_full() {
+                return Ok(false);
+            }
+
+            lane.push_back(message.clone());
+        }
+
+        drop(state);
+        self.shared.message_notify.notify_one();
+        Ok(true)
+    }
+
+    // Public methods
+    /// Discards every queued message and queues `message` alone, returning the number of discarded messages.
+    pub fn clear_and_push(&self, message: impl Into<WsIoOutboundMessage>) -> Result<usize> {
+        let message = message.into();
+        let mut state = self.shared.state.lock();
+        if state.closed {
+            bail!("Outbound queue closed");
+        }
+
+        let discarded = state.clear();
+        state.lane(message.priority).push_back(message);
+        drop(state);
+        self.shared.message_notify.notify_one();
+        Ok(discarded)
+    }
+
+    /// Queues `message` once there is room in its lane, returning `false` if none frees up within `wait_timeout`.
+    pub async fn push(&self, message: impl Into<WsIoOutboundMessage>, wait_timeout: Option<Duration>) -> Result<bool> {
+        let message = message.into();
+        match wait_timeout {
+            Some(wait_timeout) => match timeout(wait_timeout, self.push_when_space(message)).await {
+                Ok(result) => result.map(|_| true),
+                Err(_) => Ok(false),
+            },
+            None => self.push_when_space(message).await.map(|_| true),
+        }
+    }
+
+    /// Queues `message`, discarding the oldest message queued in its lane if the lane is full.
+    ///
+    /// Returns whether a message was discarded.
+    pub fn push_evicting_oldest(&self, message: impl Into<WsIoOutboundMessage>) -> Result<bool> {
+        let message = message.into();
+        let mut state = self.shared.state.lock();
+        if state.closed {
+            bail!("Outbound queue closed");
+        }
+
+        let mut evicted = false;
+        let lane = state.lane(message.priority);
+        if !lane.try_coalesce(&message) {
+            evicted = lane.is_full() && lane.pop_front().is_some();
+            lane.push_back(message);
+        }
+
+        drop(state);
+        self.shared.message_notify.notify_one();
+        Ok(evicted)
+    }
+
+    /// Queues `message` if there is room in its lane right away, returning `false` otherwise.
+    #[inline]
+    pub fn try_push(&self, message: impl Into<WsIoOutboundMessage>) -> Result<bool> {
+        self.try_push_inner(&message.into())
+    }
+}
+
+/// Receiving half of an outbound queue, owned by the task writing to the peer.
+pub struct WsIoOutboundQueueReceiver {
+    shared: Arc<SharedQueue>,
+}
+
+impl Drop for WsIoOutboundQueueReceiver {
+    fn drop(&mut self) {
+        self.shared.state.lock().clear();
+        self.shared.close();
+    }
+}
+
+impl WsIoOutboundQueueReceiver {
+    // Public methods
+    /// Discards every queued message.
+    pub fn clear(&mut self) {
+        self.shared.state.lock().clear();
+        self.shared.space_notify.notify_waiters();
+    }
+
+    /// Waits for the next message, taken from the highest priority lane holding one, returning `None` once the queue
+    /// is closed and drained.
+    pub async fn recv(&mut self) -> Option<WsIoOutboundMessage> {
+        loop {
+            {
+                let mut state = self.shared.state.lock();
+                if let Some(message) = state.pop_front() {
+                    drop(state);
+                    self.shared.space_notify.notify_waiters();
+                    return Some(message);
+                }
+
+                if state.closed {
+                    return None;
+                }
+            }
+
+            self.shared.message_notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> Arc<Message> {
+        Arc::new(Message::Text(text.into()))
+    }
+
+    async fn recv_text(message_rx: &mut WsIoOutboundQueueReceiver) -> String {
+        match &**message_rx.recv().await.unwrap().message() {
+            Message::Text(text) => text.to_string(),
+            message => panic!("Unexpected message {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn clears_the_backlog_before_pushing() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(16);
+        for text in ["a", "b", "c"] {
+            assert!(queue.try_push(message(text)).unwrap());
+        }
+
+        assert_eq!(queue.clear_and_push(message("close")).unwrap(), 3);
+        assert_eq!(recv_text(&mut message_rx).await, "close");
+    }
+
+    #[tokio::test]
+    async fn coalesces_queued_messages_in_place() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(16);
+        let coalescing_key = Arc::<str>::from("position");
+        assert!(queue.try_push(message("a")).unwrap());
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::coalesced(message("1"), coalescing_key.clone()))
+                .unwrap()
+        );
+        assert!(queue.try_push(message("b")).unwrap());
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::coalesced(message("2"), coalescing_key.clone()))
+                .unwrap()
+        );
+
+        assert_eq!(recv_text(&mut message_rx).await, "a");
+        assert_eq!(recv_text(&mut message_rx).await, "2");
+
+        // Once sent, the next message with the key is queued again instead of replacing anything
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::coalesced(message("3"), coalescing_key))
+                .unwrap()
+        );
+        assert_eq!(recv_text(&mut message_rx).await, "b");
+        assert_eq!(recv_text(&mut message_rx).await, "3");
+    }
+
+    #[tokio::test]
+    async fn coalesces_into_full_queues() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(2);
+        let coalescing_key = Arc::<str>::from("position");
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::coalesced(message("1"), coalescing_key.clone()))
+                .unwrap()
+        );
+        assert!(
+            !queue
+                .push_evicting_oldest(WsIoOutboundMessage::coalesced(message("2"), coalescing_key.clone()))
+                .unwrap()
+        );
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::coalesced(message("3"), coalescing_key))
+                .unwrap()
+        );
+
+        assert_eq!(recv_text(&mut message_rx).await, "3");
+    }
+
+    #[tokio::test]
+    async fn forgets_coalescing_keys_of_evicted_messages() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(3);
+        let coalescing_key = Arc::<str>::from("position");
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::coalesced(message("1"), coalescing_key.clone()))
+                .unwrap()
+        );
+        assert!(queue.try_push(message("a")).unwrap());
+        assert!(queue.push_evicting_oldest(message("b")).unwrap());
+        assert!(
+            queue
+                .push_evicting_oldest(WsIoOutboundMessage::coalesced(message("2"), coalescing_key))
+                .unwrap()
+        );
+
+        assert_eq!(recv_text(&mut message_rx).await, "b");
+        assert_eq!(recv_text(&mut message_rx).await, "2");
+    }
+
+    #[tokio::test]
+    async fn drains_queued_messages_once_closed() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(16);
+        assert!(queue.try_push(message("a")).unwrap());
+        drop(queue);
+
+        assert_eq!(recv_text(&mut message_rx).await, "a");
+        assert!(message_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn drains_the_high_priority_lane_first() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(16);
+        assert!(queue.try_push(message("a")).unwrap());
+        assert!(queue.try_push(message("b")).unwrap());
+        assert!(
+            queue
+                .try_push(WsIoOutboundMessage::from(message("ack")).with_priority(WsIoOutboundPriority::High))
+                .unwrap()
+        );
+
+        assert_eq!(recv_text(&mut message_rx).await, "ack");
+        assert_eq!(recv_text(&mut message_rx).await, "a");
+        assert_eq!(recv_text(&mut message_rx).await, "b");
+    }
+
+    #[tokio::test]
+    async fn evicts_the_oldest_message_when_full() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(8);
+        for index in 0..7 {
+            assert!(!queue.push_evicting_oldest(message(&index.to_string())).unwrap());
+        }
+
+        assert!(queue.push_evicting_oldest(message("7")).unwrap());
+        for index in 1..8 {
+            assert_eq!(recv_text(&mut message_rx).await, index.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_messages_when_full() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(8);
+        for index in 0..7 {
+            assert!(queue.try_push(message(&index.to_string())).unwrap());
+        }
+
+        assert!(!queue.try_push(message("7")).unwrap());
+        assert!(!queue.push(message("7"), Some(Duration::from_millis(1))).await.unwrap());
+
+        assert_eq!(recv_text(&mut message_rx).await, "0");
+        assert!(queue.try_push(message("7")).unwrap());
+    }
+
+    #[tokio::test]
+    async fn splits_the_capacity_between_lanes() {
+        let (queue, _message_rx) = WsIoOutboundQueue::new(16);
+        for _ in 0..14 {
+            assert!(queue.try_push(message("event")).unwrap());
+        }
+
+        assert!(!queue.try_push(message("event")).unwrap());
+
+        // A full normal lane does not hold back control packets, which have a lane of their own
+        let control_message = || WsIoOutboundMessage::from(message("ack")).with_priority(WsIoOutboundPriority::High);
+        assert!(queue.try_push(control_message()).unwrap());
+        assert!(queue.try_push(control_message()).unwrap());
+        assert!(!queue.try_push(control_message()).unwrap());
+        assert!(queue.push_evicting_oldest(control_message()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn wakes_blocked_pushes_when_space_frees_up() {
+        let (queue, mut message_rx) = WsIoOutboundQueue::new(2);
+        assert!(queue.try_push(message("a")).unwrap());
+
+        let (pushed, received) = tokio::join!(queue.push(message("b"), Some(Duration::from_secs(60))), async {
+            recv_text(&mut message_rx).await
+        });
+
+        assert!(pushed.unwrap());
+        assert_eq!(received, "a");
+        assert_eq!(recv_text(&mut message_rx).await, "b");
+    }
+}
@@ -1,7 +1,12 @@
+use std::any::Any;
+
 use ::bincode::{
     config::standard,
     serde::{
+        BorrowedSerdeDecoder,
+        borrow_decode_from_slice,
         decode_from_slice,
+        encode_into_std_write,
         encode_to_vec,
     },
 };
@@ -12,26 +17,23 @@ use serde::{
 };
 
 use super::super::{
-    InnerPacket,
-    InnerPacketRef,
+    InnerPacketBody,
+    InnerPacketHeader,
     WsIoPacket,
 };
+use crate::traits::packet::codec::{
+    ErasedPacketData,
+    ErasedPacketDataDecoder,
+    PacketCodec,
+};
 
 // Structs
-pub(super) struct WsIoPacketBincodeCodec;
+#[derive(Debug)]
+pub struct WsIoPacketBincodeCodec;
 
 impl WsIoPacketBincodeCodec {
     pub(super) const IS_TEXT: bool = false;
-
-    #[inline]
-    pub(super) fn decode(&self, bytes: &[u8]) -> Result<WsIoPacket> {
-        let (inner_packet, _) = decode_from_slice::<InnerPacket, _>(bytes, standard())?;
-        Ok(WsIoPacket {
-            data: inner_packet.0,
-            key: inner_packet.1,
-            r#type: inner_packet.2,
-        })
-    }
+    pub(super) const NAME: &str = "bincode";
 
     #[inline]
     pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
@@ -40,15 +42,43 @@ impl WsIoPacketBincodeCodec {
     }
 
     #[inline]
-    pub(super) fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
-        Ok(encode_to_vec(
-            InnerPacketRef(&packet.data, &packet.key, &packet.r#type),
-            standard(),
-        )?)
+    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
+        Ok(encode_to_vec(data, standard())?)
     }
+}
 
+impl PacketCodec for WsIoPacketBincodeCodec {
     #[inline]
-    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
-        Ok(encode_to_vec(data, standard())?)
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>> {
+        let (header, header_len) = borrow_decode_from_slice::<InnerPacketHeader, _>(bytes, standard())?;
+        let body = borrow_decode_from_slice::<InnerPacketBody, _>(&bytes[header_len..], standard());
+        WsIoPacket::from_header_and_body(header, body.map(|(body, _)| body))
+    }
+
+    #[inline]
+    fn decode_data(&self, bytes: &[u8], data_decoder: ErasedPacketDataDecoder) -> Result<Box<dyn Any>> {
+        data_decoder.decode(BorrowedSerdeDecoder::from_slice(bytes, standard(), ()).as_deserializer())
+    }
+
+    #[inline]
+    fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
+        let mut bytes = encode_to_vec(packet.header_ref(), standard())?;
+        encode_into_std_write(packet.body_ref(), &mut bytes, standard())?;
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn encode_data(&self, data: &ErasedPacketData) -> Result<Vec<u8>> {
+        self.encode_data(data)
+    }
+
+    #[inline]
+    fn is_text(&self) -> bool {
+        Self::IS_TEXT
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
     }
 }
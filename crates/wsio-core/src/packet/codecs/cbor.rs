@@ -1,44 +1,158 @@
-use std::io::Cursor;
+use std::{
+    any::Any,
+    borrow::Cow,
+    cell::Cell,
+    io::Cursor,
+};
 
-use anyhow::Result;
+use anyhow::{
+    Result,
+    anyhow,
+};
 use ciborium::{
     de::from_reader,
     ser::into_writer,
 };
 use serde::{
+    Deserialize,
+    Deserializer,
     Serialize,
-    de::DeserializeOwned,
+    de::{
+        DeserializeOwned,
+        Error,
+    },
+};
+use serde_with::{
+    Bytes,
+    serde_as,
 };
 
-use super::super::WsIoPacket;
+use super::super::{
+    WsIoPacket,
+    WsIoPacketType,
+};
+use crate::traits::packet::codec::{
+    ErasedPacketData,
+    ErasedPacketDataDecoder,
+    PacketCodec,
+};
 
 // Structs
-pub(super) struct WsIoPacketCborCodec;
+/// `ciborium` only deserializes owned values, so the envelope cannot borrow from the frame.
+#[serde_as]
+#[derive(Deserialize)]
+struct InnerPacket {
+    #[serde(rename = "a")]
+    ack_id: Option<u32>,
+
+    #[serde(default, rename = "b")]
+    #[serde_as(as = "Vec<Bytes>")]
+    attachments: Vec<Vec<u8>>,
+
+    #[serde(rename = "d")]
+    #[serde_as(as = "Option<Bytes>")]
+    data: Option<Vec<u8>>,
+
+    #[serde(rename = "k")]
+    key: Option<String>,
+
+    #[serde(rename = "m")]
+    message: Option<String>,
+
+    #[serde(rename = "s")]
+    stream_id: Option<u32>,
+
+    #[serde(rename = "t")]
+    r#type: WsIoPacketType,
+
+    #[serde(rename = "v")]
+    version: Option<u16>,
+}
+
+/// `ciborium` does not expose its deserializer, so the erased decoder is handed over through a thread local.
+struct ErasedData(Box<dyn Any>);
+
+impl<'de> Deserialize<'de> for ErasedData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data_decoder = ERASED_DATA_DECODER
+            .take()
+            .ok_or_else(|| D::Error::custom("Missing erased data decoder"))?;
+
+        Ok(Self(data_decoder.decode(deserializer).map_err(D::Error::custom)?))
+    }
+}
+
+#[derive(Debug)]
+pub struct WsIoPacketCborCodec;
 
 impl WsIoPacketCborCodec {
     pub(super) const IS_TEXT: bool = false;
+    pub(super) const NAME: &str = "cbor";
 
     #[inline]
-    pub(super) fn decode(&self, bytes: &[u8]) -> Result<WsIoPacket> {
+    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
         Ok(from_reader(Cursor::new(bytes))?)
     }
 
     #[inline]
-    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
-        Ok(from_reader(Cursor::new(bytes))?)
+    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        into_writer(data, &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+impl PacketCodec for WsIoPacketCborCodec {
+    #[inline]
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>> {
+        let inner_packet = from_reader::<InnerPacket, _>(Cursor::new(bytes))?;
+        Ok(WsIoPacket {
+            ack_id: inner_packet.ack_id,
+            attachments: inner_packet.attachments.into_iter().map(Cow::Owned).collect(),
+            data: inner_packet.data.map(Cow::Owned),
+            key: inner_packet.key.map(Cow::Owned),
+            message: inner_packet.message.map(Cow::Owned),
+            stream_id: inner_packet.stream_id,
+            r#type: inner_packet.r#type,
+            version: inner_packet.version,
+        })
+    }
+
+    #[inline]
+    fn decode_data(&self, bytes: &[u8], data_decoder: ErasedPacketDataDecoder) -> Result<Box<dyn Any>> {
+        ERASED_DATA_DECODER.set(Some(data_decoder));
+        let data = from_reader::<ErasedData, _>(Cursor::new(bytes))
+            .map(|data| data.0)
+            .map_err(|err| anyhow!(err));
+
+        ERASED_DATA_DECODER.set(None);
+        data
     }
 
     #[inline]
-    pub(super) fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
+    fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
         let mut buffer = Vec::new();
         into_writer(packet, &mut buffer)?;
         Ok(buffer)
     }
 
     #[inline]
-    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
-        let mut buffer = Vec::new();
-        into_writer(data, &mut buffer)?;
-        Ok(buffer)
+    fn encode_data(&self, data: &ErasedPacketData) -> Result<Vec<u8>> {
+        self.encode_data(data)
+    }
+
+    #[inline]
+    fn is_text(&self) -> bool {
+        Self::IS_TEXT
     }
+
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+// Constants/Statics
+thread_local! {
+    static ERASED_DATA_DECODER: Cell<Option<ErasedPacketDataDecoder>> = const { Cell::new(None) };
 }
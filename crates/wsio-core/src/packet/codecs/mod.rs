@@ -1,4 +1,13 @@
-use anyhow::Result;
+use std::{
+    borrow::Cow,
+    sync::Arc,
+};
+
+use anyhow::{
+    Result,
+    anyhow,
+    bail,
+};
 use serde::{
     Serialize,
     de::DeserializeOwned,
@@ -22,20 +31,25 @@ mod serde_json;
 mod sonic_rs;
 
 #[cfg(feature = "packet-codec-bincode")]
-use self::bincode::WsIoPacketBincodeCodec;
+pub use self::bincode::WsIoPacketBincodeCodec;
 #[cfg(feature = "packet-codec-cbor")]
-use self::cbor::WsIoPacketCborCodec;
+pub use self::cbor::WsIoPacketCborCodec;
 #[cfg(feature = "packet-codec-msgpack")]
-use self::msgpack::WsIoPacketMsgPackCodec;
+pub use self::msgpack::WsIoPacketMsgPackCodec;
 #[cfg(feature = "packet-codec-postcard")]
-use self::postcard::WsIoPacketPostcardCodec;
-use self::serde_json::WsIoPacketSerdeJsonCodec;
+pub use self::postcard::WsIoPacketPostcardCodec;
+pub use self::serde_json::WsIoPacketSerdeJsonCodec;
 #[cfg(feature = "packet-codec-sonic-rs")]
-use self::sonic_rs::WsIoPacketSonicRsCodec;
+pub use self::sonic_rs::WsIoPacketSonicRsCodec;
 use super::WsIoPacket;
+use crate::traits::packet::codec::{
+    ErasedPacketData,
+    ErasedPacketDataDecoder,
+    PacketCodec,
+};
 
 // Enums
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum WsIoPacketCodec {
     #[cfg(feature = "packet-codec-bincode")]
     Bincode,
@@ -43,6 +57,9 @@ pub enum WsIoPacketCodec {
     #[cfg(feature = "packet-codec-cbor")]
     Cbor,
 
+    /// User-defined codec, see [`PacketCodec`].
+    Custom(Arc<dyn PacketCodec>),
+
     #[cfg(feature = "packet-codec-msgpack")]
     MsgPack,
 
@@ -56,8 +73,34 @@ pub enum WsIoPacketCodec {
 }
 
 impl WsIoPacketCodec {
+    // Private methods
+    #[inline]
+    fn encode_envelope(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "packet-codec-bincode")]
+            Self::Bincode => WsIoPacketBincodeCodec.encode(packet),
+
+            #[cfg(feature = "packet-codec-cbor")]
+            Self::Cbor => WsIoPacketCborCodec.encode(packet),
+
+            Self::Custom(packet_codec) => packet_codec.encode(packet),
+
+            #[cfg(feature = "packet-codec-msgpack")]
+            Self::MsgPack => WsIoPacketMsgPackCodec.encode(packet),
+
+            #[cfg(feature = "packet-codec-postcard")]
+            Self::Postcard => WsIoPacketPostcardCodec.encode(packet),
+
+            Self::SerdeJson => WsIoPacketSerdeJsonCodec.encode(packet),
+
+            #[cfg(feature = "packet-codec-sonic-rs")]
+            Self::SonicRs => WsIoPacketSonicRsCodec.encode(packet),
+        }
+    }
+
+    // Public methods
     #[inline]
-    pub fn decode(&self, bytes: &[u8]) -> Result<WsIoPacket> {
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>> {
         match self {
             #[cfg(feature = "packet-codec-bincode")]
             Self::Bincode => WsIoPacketBincodeCodec.decode(bytes),
@@ -65,6 +108,8 @@ impl WsIoPacketCodec {
             #[cfg(feature = "packet-codec-cbor")]
             Self::Cbor => WsIoPacketCborCodec.decode(bytes),
 
+            Self::Custom(packet_codec) => packet_codec.decode(bytes),
+
             #[cfg(feature = "packet-codec-msgpack")]
             Self::MsgPack => WsIoPacketMsgPackCodec.decode(bytes),
 
@@ -78,8 +123,26 @@ impl WsIoPacketCodec {
         }
     }
 
+    /// Decodes a packet from a received frame; text codecs receive packets with attachments in binary frames, see
+    /// [`encode`](Self::encode).
+    pub fn decode_frame<'a>(&self, frame: &'a [u8], is_binary: bool) -> Result<WsIoPacket<'a>> {
+        if !is_binary || !self.is_text() {
+            return self.decode(frame);
+        }
+
+        let mut frame = frame;
+        let mut packet = self.decode(take_length_prefixed(&mut frame)?)?;
+        while !frame.is_empty() {
+            packet
+                .attachments
+                .push(Cow::Borrowed(take_length_prefixed(&mut frame)?));
+        }
+
+        Ok(packet)
+    }
+
     #[inline]
-    pub fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
+    pub fn decode_data<D: DeserializeOwned + 'static>(&self, bytes: &[u8]) -> Result<D> {
         match self {
             #[cfg(feature = "packet-codec-bincode")]
             Self::Bincode => WsIoPacketBincodeCodec.decode_data(bytes),
@@ -87,6 +150,12 @@ impl WsIoPacketCodec {
             #[cfg(feature = "packet-codec-cbor")]
             Self::Cbor => WsIoPacketCborCodec.decode_data(bytes),
 
+            Self::Custom(packet_codec) => packet_codec
+                .decode_data(bytes, ErasedPacketDataDecoder::new::<D>())?
+                .downcast()
+                .map(|data| *data)
+                .map_err(|_| anyhow!("Custom packet codec decoded data into an unexpected type")),
+
             #[cfg(feature = "packet-codec-msgpack")]
             Self::MsgPack => WsIoPacketMsgPackCodec.decode_data(bytes),
 
@@ -100,26 +169,28 @@ impl WsIoPacketCodec {
         }
     }
 
-    #[inline]
+    /// Encodes a packet into a frame.
+    ///
+    /// Text codecs cannot embed binary attachments, so packets carrying some are sent in a binary frame instead,
+    /// holding the envelope and then each attachment, all prefixed with their length as a big-endian `u32`.
     pub fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
-        match self {
-            #[cfg(feature = "packet-codec-bincode")]
-            Self::Bincode => WsIoPacketBincodeCodec.encode(packet),
-
-            #[cfg(feature = "packet-codec-cbor")]
-            Self::Cbor => WsIoPacketCborCodec.encode(packet),
-
-            #[cfg(feature = "packet-codec-msgpack")]
-            Self::MsgPack => WsIoPacketMsgPackCodec.encode(packet),
-
-            #[cfg(feature = "packet-codec-postcard")]
-            Self::Postcard => WsIoPacketPostcardCodec.encode(packet),
-
-            Self::SerdeJson => WsIoPacketSerdeJsonCodec.encode(packet),
+        let envelope = self.encode_envelope(packet)?;
+        if !self.is_text() || packet.attachments.is_empty() {
+            return Ok(envelope);
+        }
 
-            #[cfg(feature = "packet-codec-sonic-rs")]
-            Self::SonicRs => WsIoPacketSonicRsCodec.encode(packet),
+        let attachments_len = packet
+            .attachments
+            .iter()
+            .map(|attachment| attachment.len() + 4)
+            .sum::<usize>();
+        let mut frame = Vec::with_capacity(envelope.len() + 4 + attachments_len);
+        put_length_prefixed(&mut frame, &envelope)?;
+        for attachment in &packet.attachments {
+            put_length_prefixed(&mut frame, attachment)?;
         }
+
+        Ok(frame)
     }
 
     #[inline]
@@ -131,6 +202,8 @@ impl WsIoPacketCodec {
             #[cfg(feature = "packet-codec-cbor")]
             Self::Cbor => WsIoPacketCborCodec.encode_data(data),
 
+            Self::Custom(packet_codec) => packet_codec.encode_data(&ErasedPacketData::new(data)),
+
             #[cfg(feature = "packet-codec-msgpack")]
             Self::MsgPack => WsIoPacketMsgPackCodec.encode_data(data),
 
@@ -144,6 +217,21 @@ impl WsIoPacketCodec {
         }
     }
 
+    /// Turns a frame encoded by this codec into the text of a text frame.
+    ///
+    /// Only the built-in text codecs are trusted to produce valid UTF-8, frames of custom codecs are checked.
+    #[inline]
+    pub fn into_text(&self, frame: Vec<u8>) -> Result<String> {
+        match self {
+            Self::SerdeJson => Ok(unsafe { String::from_utf8_unchecked(frame) }),
+
+            #[cfg(feature = "packet-codec-sonic-rs")]
+            Self::SonicRs => Ok(unsafe { String::from_utf8_unchecked(frame) }),
+
+            _ => String::from_utf8(frame).map_err(|_| anyhow!("Packet codec '{}' produced invalid UTF-8", self.name())),
+        }
+    }
+
     #[inline]
     pub fn is_text(&self) -> bool {
         match self {
@@ -153,6 +241,8 @@ impl WsIoPacketCodec {
             #[cfg(feature = "packet-codec-cbor")]
             Self::Cbor => WsIoPacketCborCodec::IS_TEXT,
 
+            Self::Custom(packet_codec) => packet_codec.is_text(),
+
             #[cfg(feature = "packet-codec-msgpack")]
             Self::MsgPack => WsIoPacketMsgPackCodec::IS_TEXT,
 
@@ -165,4 +255,251 @@ impl WsIoPacketCodec {
             Self::SonicRs => WsIoPacketSonicRsCodec::IS_TEXT,
         }
     }
+
+    /// Whether `packet` is sent in a text frame, which is the case for text codecs unless it carries attachments.
+    #[inline]
+    pub fn is_text_frame(&self, packet: &WsIoPacket) -> bool {
+        self.is_text() && packet.attachments.is_empty()
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        match self {
+            #[cfg(feature = "packet-codec-bincode")]
+            Self::Bincode => WsIoPacketBincodeCodec::NAME,
+
+            #[cfg(feature = "packet-codec-cbor")]
+            Self::Cbor => WsIoPacketCborCodec::NAME,
+
+            Self::Custom(packet_codec) => packet_codec.name(),
+
+            #[cfg(feature = "packet-codec-msgpack")]
+            Self::MsgPack => WsIoPacketMsgPackCodec::NAME,
+
+            #[cfg(feature = "packet-codec-postcard")]
+            Self::Postcard => WsIoPacketPostcardCodec::NAME,
+
+            Self::SerdeJson => WsIoPacketSerdeJsonCodec::NAME,
+
+            #[cfg(feature = "packet-codec-sonic-rs")]
+            Self::SonicRs => WsIoPacketSonicRsCodec::NAME,
+        }
+    }
+
+    /// WebSocket sub-protocol used to negotiate this codec during the handshake.
+    #[inline]
+    pub fn sub_protocol(&self) -> String {
+        format!("wsio-{}", self.name())
+    }
+}
+
+impl<C: PacketCodec> From<C> for WsIoPacketCodec {
+    #[inline]
+    fn from(packet_codec: C) -> Self {
+        Self::Custom(Arc::new(packet_codec))
+    }
+}
+
+// Functions
+#[inline]
+fn put_length_prefixed(frame: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
+    frame.extend_from_slice(&u32::try_from(bytes.len())?.to_be_bytes());
+    frame.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn take_length_prefixed<'a>(frame: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let Some((len, rest)) = frame.split_first_chunk::<4>() else {
+        bail!("Truncated attachment frame");
+    };
+
+    let len = u32::from_be_bytes(*len) as usize;
+    if rest.len() < len {
+        bail!("Truncated attachment frame");
+    }
+
+    let (bytes, rest) = rest.split_at(len);
+    *frame = rest;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::packet::WsIoPacketType;
+
+    fn codecs() -> Vec<WsIoPacketCodec> {
+        vec![
+            #[cfg(feature = "packet-codec-bincode")]
+            WsIoPacketCodec::Bincode,
+            #[cfg(feature = "packet-codec-cbor")]
+            WsIoPacketCodec::Cbor,
+            #[cfg(feature = "packet-codec-msgpack")]
+            WsIoPacketCodec::MsgPack,
+            #[cfg(feature = "packet-codec-postcard")]
+            WsIoPacketCodec::Postcard,
+            WsIoPacketCodec::SerdeJson,
+            #[cfg(feature = "packet-codec-sonic-rs")]
+            WsIoPacketCodec::SonicRs,
+        ]
+    }
+
+    fn round_trip<'a>(packet_codec: &WsIoPacketCodec, frame: &'a [u8], packet: &WsIoPacket) -> WsIoPacket<'a> {
+        packet_codec
+            .decode_frame(frame, !packet_codec.is_text_frame(packet))
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_every_envelope_field() {
+        for packet_codec in codecs() {
+            let data = packet_codec.encode_data(&vec![1u32, 2, 3]).unwrap();
+            let packet = WsIoPacket {
+                ack_id: Some(7),
+                message: Some(Cow::Borrowed("failed")),
+                stream_id: Some(3),
+                version: Some(9),
+                ..WsIoPacket::new_event_with_attachments("chat", Some(data), vec![b"\x00\xff", b""])
+            };
+
+            let frame = packet_codec.encode(&packet).unwrap();
+            let decoded = round_trip(&packet_codec, &frame, &packet);
+            let name = packet_codec.name();
+            assert_eq!(decoded.ack_id, Some(7), "{name}");
+            assert_eq!(decoded.attachments, packet.attachments, "{name}");
+            assert_eq!(
+                packet_codec
+                    .decode_data::<Vec<u32>>(decoded.data.as_deref().unwrap())
+                    .unwrap(),
+                [1, 2, 3],
+                "{name}"
+            );
+            assert_eq!(decoded.key.as_deref(), Some("chat"), "{name}");
+            assert_eq!(decoded.message.as_deref(), Some("failed"), "{name}");
+            assert_eq!(decoded.stream_id, Some(3), "{name}");
+            assert_eq!(decoded.r#type, WsIoPacketType::Event, "{name}");
+            assert_eq!(decoded.version, Some(9), "{name}");
+        }
+    }
+
+    #[test]
+    fn round_trips_packets_without_optional_fields() {
+        for packet_codec in codecs() {
+            let packet = WsIoPacket::new_ping();
+            let frame = packet_codec.encode(&packet).unwrap();
+            let decoded = round_trip(&packet_codec, &frame, &packet);
+            assert_eq!(decoded.r#type, WsIoPacketType::Ping, "{}", packet_codec.name());
+            assert!(decoded.attachments.is_empty() && decoded.data.is_none() && decoded.key.is_none());
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_packet_types() {
+        for packet_codec in codecs() {
+            let packet = WsIoPacket::new(WsIoPacketType::Unknown(200), None, None);
+            let frame = packet_codec.encode(&packet).unwrap();
+            let decoded = round_trip(&packet_codec, &frame, &packet);
+            assert_eq!(decoded.r#type, WsIoPacketType::Unknown(200), "{}", packet_codec.name());
+        }
+    }
+
+    #[test]
+    fn decodes_init_packets_of_other_protocol_versions() {
+        // Text envelopes ignore fields they do not know
+        let decoded = WsIoPacketCodec::SerdeJson
+            .decode(br#"{"t":2,"v":99,"z":[1,2]}"#)
+            .unwrap();
+        assert_eq!((decoded.r#type, decoded.version), (WsIoPacketType::Init, Some(99)));
+
+        // Binary envelopes keep the header readable whatever follows it
+        #[cfg(feature = "packet-codec-bincode")]
+        assert_init_with_foreign_body(
+            WsIoPacketCodec::Bincode,
+            ::bincode::serde::encode_to_vec(
+                crate::packet::InnerPacketHeaderRef(&WsIoPacketType::Init, &Some(99)),
+                ::bincode::config::standard(),
+            )
+            .unwrap(),
+        );
+
+        #[cfg(feature = "packet-codec-msgpack")]
+        assert_init_with_foreign_body(
+            WsIoPacketCodec::MsgPack,
+            rmp_serde::to_vec(&crate::packet::InnerPacketHeaderRef(&WsIoPacketType::Init, &Some(99))).unwrap(),
+        );
+
+        #[cfg(feature = "packet-codec-postcard")]
+        assert_init_with_foreign_body(
+            WsIoPacketCodec::Postcard,
+            ::postcard::to_allocvec(&crate::packet::InnerPacketHeaderRef(&WsIoPacketType::Init, &Some(99))).unwrap(),
+        );
+    }
+
+    #[cfg(any(
+        feature = "packet-codec-bincode",
+        feature = "packet-codec-msgpack",
+        feature = "packet-codec-postcard"
+    ))]
+    fn assert_init_with_foreign_body(packet_codec: WsIoPacketCodec, mut frame: Vec<u8>) {
+        let header_len = frame.len();
+        frame.extend_from_slice(&[0xff; 16]);
+        let decoded = packet_codec.decode(&frame).unwrap();
+        assert_eq!(
+            (decoded.r#type, decoded.version),
+            (WsIoPacketType::Init, Some(99)),
+            "{}",
+            packet_codec.name()
+        );
+
+        // Other packets with an unreadable body are still rejected
+        frame[..header_len].copy_from_slice(&packet_codec.encode(&WsIoPacket::new_ready()).unwrap()[..header_len]);
+        assert!(packet_codec.decode(&frame).is_err(), "{}", packet_codec.name());
+    }
+
+    #[test]
+    fn rejects_truncated_attachment_frames() {
+        let packet_codec = WsIoPacketCodec::SerdeJson;
+        let packet = WsIoPacket::new_event_with_attachments("chat", None, vec![b"blob"]);
+        let frame = packet_codec.encode(&packet).unwrap();
+        assert!(packet_codec.decode_frame(&frame[..frame.len() - 1], true).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_utf8_from_custom_text_codecs() {
+        #[derive(Debug)]
+        struct InvalidTextCodec;
+
+        impl PacketCodec for InvalidTextCodec {
+            fn decode<'a>(&self, _: &'a [u8]) -> Result<WsIoPacket<'a>> {
+                unimplemented!()
+            }
+
+            fn decode_data(&self, _: &[u8], _: ErasedPacketDataDecoder) -> Result<Box<dyn std::any::Any>> {
+                unimplemented!()
+            }
+
+            fn encode(&self, _: &WsIoPacket) -> Result<Vec<u8>> {
+                Ok(vec![0xff])
+            }
+
+            fn encode_data(&self, _: &ErasedPacketData) -> Result<Vec<u8>> {
+                unimplemented!()
+            }
+
+            fn is_text(&self) -> bool {
+                true
+            }
+
+            fn name(&self) -> &str {
+                "invalid"
+            }
+        }
+
+        let packet_codec = WsIoPacketCodec::from(InvalidTextCodec);
+        let frame = packet_codec.encode(&WsIoPacket::new_ping()).unwrap();
+        assert!(packet_codec.into_text(frame).is_err());
+        assert!(WsIoPacketCodec::SerdeJson.into_text(b"{}".to_vec()).is_ok());
+    }
 }
@@ -1,47 +1,80 @@
+use std::any::Any;
+
 use anyhow::Result;
 use rmp_serde::{
+    Deserializer,
+    encode::write,
     from_slice,
     to_vec,
 };
 use serde::{
+    Deserialize,
     Serialize,
     de::DeserializeOwned,
 };
 
 use super::super::{
-    InnerPacket,
-    InnerPacketRef,
+    InnerPacketBody,
+    InnerPacketHeader,
     WsIoPacket,
 };
+use crate::traits::packet::codec::{
+    ErasedPacketData,
+    ErasedPacketDataDecoder,
+    PacketCodec,
+};
 
 // Structs
-pub(super) struct WsIoPacketMsgPackCodec;
+#[derive(Debug)]
+pub struct WsIoPacketMsgPackCodec;
 
 impl WsIoPacketMsgPackCodec {
     pub(super) const IS_TEXT: bool = false;
+    pub(super) const NAME: &str = "msgpack";
 
     #[inline]
-    pub(super) fn decode(&self, bytes: &[u8]) -> Result<WsIoPacket> {
-        let inner_packet = from_slice::<InnerPacket>(bytes)?;
-        Ok(WsIoPacket {
-            data: inner_packet.0,
-            key: inner_packet.1,
-            r#type: inner_packet.2,
-        })
+    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
+        Ok(from_slice(bytes)?)
     }
 
     #[inline]
-    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
-        Ok(from_slice(bytes)?)
+    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
+        Ok(to_vec(data)?)
     }
+}
 
+impl PacketCodec for WsIoPacketMsgPackCodec {
     #[inline]
-    pub(super) fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
-        Ok(to_vec(&InnerPacketRef(&packet.data, &packet.key, &packet.r#type))?)
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>> {
+        let mut deserializer = Deserializer::from_read_ref(bytes);
+        let header = InnerPacketHeader::deserialize(&mut deserializer)?;
+        WsIoPacket::from_header_and_body(header, InnerPacketBody::deserialize(&mut deserializer))
     }
 
     #[inline]
-    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
-        Ok(to_vec(data)?)
+    fn decode_data(&self, bytes: &[u8], data_decoder: ErasedPacketDataDecoder) -> Result<Box<dyn Any>> {
+        data_decoder.decode(&mut Deserializer::from_read_ref(bytes))
+    }
+
+    #[inline]
+    fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
+        let mut bytes = to_vec(&packet.header_ref())?;
+        write(&mut bytes, &packet.body_ref())?;
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn encode_data(&self, data: &ErasedPacketData) -> Result<Vec<u8>> {
+        self.encode_data(data)
+    }
+
+    #[inline]
+    fn is_text(&self) -> bool {
+        Self::IS_TEXT
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
     }
 }
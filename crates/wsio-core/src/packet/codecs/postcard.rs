@@ -1,7 +1,12 @@
+use std::any::Any;
+
 use anyhow::Result;
 use postcard::{
+    Deserializer,
     from_bytes,
+    take_from_bytes,
     to_allocvec,
+    to_extend,
 };
 use serde::{
     Serialize,
@@ -9,39 +14,64 @@ use serde::{
 };
 
 use super::super::{
-    InnerPacket,
-    InnerPacketRef,
+    InnerPacketBody,
+    InnerPacketHeader,
     WsIoPacket,
 };
+use crate::traits::packet::codec::{
+    ErasedPacketData,
+    ErasedPacketDataDecoder,
+    PacketCodec,
+};
 
 // Structs
-pub(super) struct WsIoPacketPostcardCodec;
+#[derive(Debug)]
+pub struct WsIoPacketPostcardCodec;
 
 impl WsIoPacketPostcardCodec {
     pub(super) const IS_TEXT: bool = false;
+    pub(super) const NAME: &str = "postcard";
 
     #[inline]
-    pub(super) fn decode(&self, bytes: &[u8]) -> Result<WsIoPacket> {
-        let inner_packet = from_bytes::<InnerPacket>(bytes)?;
-        Ok(WsIoPacket {
-            data: inner_packet.0,
-            key: inner_packet.1,
-            r#type: inner_packet.2,
-        })
+    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
+        Ok(from_bytes::<D>(bytes)?)
     }
 
     #[inline]
-    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
-        Ok(from_bytes::<D>(bytes)?)
+    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
+        Ok(to_allocvec(data)?)
     }
+}
 
+impl PacketCodec for WsIoPacketPostcardCodec {
     #[inline]
-    pub(super) fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
-        Ok(to_allocvec(&InnerPacketRef(&packet.data, &packet.key, &packet.r#type))?)
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>> {
+        let (header, body_bytes) = take_from_bytes::<InnerPacketHeader>(bytes)?;
+        WsIoPacket::from_header_and_body(header, from_bytes::<InnerPacketBody>(body_bytes))
     }
 
     #[inline]
-    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
-        Ok(to_allocvec(data)?)
+    fn decode_data(&self, bytes: &[u8], data_decoder: ErasedPacketDataDecoder) -> Result<Box<dyn Any>> {
+        data_decoder.decode(&mut Deserializer::from_bytes(bytes))
+    }
+
+    #[inline]
+    fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
+        Ok(to_extend(&packet.body_ref(), to_allocvec(&packet.header_ref())?)?)
+    }
+
+    #[inline]
+    fn encode_data(&self, data: &ErasedPacketData) -> Result<Vec<u8>> {
+        self.encode_data(data)
+    }
+
+    #[inline]
+    fn is_text(&self) -> bool {
+        Self::IS_TEXT
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
     }
 }
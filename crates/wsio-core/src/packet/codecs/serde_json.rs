@@ -1,38 +1,164 @@
+use std::{
+    any::Any,
+    borrow::Cow,
+};
+
 use ::serde_json::{
+    Deserializer as JsonDeserializer,
     from_slice,
     to_vec,
+    value::RawValue,
 };
 use anyhow::Result;
 use serde::{
+    Deserialize,
+    Deserializer,
     Serialize,
     de::DeserializeOwned,
 };
+use serde_with::{
+    BorrowCow,
+    serde_as,
+    skip_serializing_none,
+};
 
-use super::super::WsIoPacket;
+use super::super::{
+    WsIoPacket,
+    WsIoPacketType,
+};
+use crate::traits::packet::codec::{
+    ErasedPacketData,
+    ErasedPacketDataDecoder,
+    PacketCodec,
+};
 
 // Structs
-pub(super) struct WsIoPacketSerdeJsonCodec;
+#[serde_as]
+#[derive(Deserialize)]
+struct InnerPacket<'a> {
+    #[serde(rename = "a")]
+    ack_id: Option<u32>,
+
+    #[serde(borrow, default, deserialize_with = "deserialize_raw_data", rename = "d")]
+    data: Option<&'a RawValue>,
+
+    #[serde(borrow, rename = "k")]
+    #[serde_as(as = "Option<BorrowCow>")]
+    key: Option<Cow<'a, str>>,
+
+    #[serde(borrow, rename = "m")]
+    #[serde_as(as = "Option<BorrowCow>")]
+    message: Option<Cow<'a, str>>,
+
+    #[serde(rename = "s")]
+    stream_id: Option<u32>,
+
+    #[serde(rename = "t")]
+    r#type: WsIoPacketType,
+
+    #[serde(rename = "v")]
+    version: Option<u16>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct InnerPacketRef<'a> {
+    #[serde(rename = "a")]
+    ack_id: Option<u32>,
+
+    #[serde(rename = "d")]
+    data: Option<&'a RawValue>,
+
+    #[serde(rename = "k")]
+    key: Option<&'a str>,
+
+    #[serde(rename = "m")]
+    message: Option<&'a str>,
+
+    #[serde(rename = "s")]
+    stream_id: Option<u32>,
+
+    #[serde(rename = "t")]
+    r#type: &'a WsIoPacketType,
+
+    #[serde(rename = "v")]
+    version: Option<u16>,
+}
+
+#[derive(Debug)]
+pub struct WsIoPacketSerdeJsonCodec;
 
 impl WsIoPacketSerdeJsonCodec {
     pub(super) const IS_TEXT: bool = true;
+    pub(super) const NAME: &str = "json";
+
+    #[inline]
+    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
+        Ok(from_slice(bytes)?)
+    }
 
     #[inline]
-    pub(super) fn decode(&self, bytes: &[u8]) -> Result<WsIoPacket> {
-        Ok(from_slice::<WsIoPacket>(bytes)?)
+    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
+        Ok(to_vec(data)?)
     }
+}
 
+impl PacketCodec for WsIoPacketSerdeJsonCodec {
     #[inline]
-    pub(super) fn decode_data<D: DeserializeOwned>(&self, bytes: &[u8]) -> Result<D> {
-        Ok(from_slice(bytes)?)
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>> {
+        let inner_packet = from_slice::<InnerPacket>(bytes)?;
+        Ok(WsIoPacket {
+            ack_id: inner_packet.ack_id,
+            attachments: Vec::new(),
+            data: inner_packet.data.map(|data| Cow::Borrowed(data.get().as_bytes())),
+            key: inner_packet.key,
+            message: inner_packet.message,
+            stream_id: inner_packet.stream_id,
+            r#type: inner_packet.r#type,
+            version: inner_packet.version,
+        })
     }
 
     #[inline]
-    pub(super) fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
-        Ok(to_vec(packet)?)
+    fn decode_data(&self, bytes: &[u8], data_decoder: ErasedPacketDataDecoder) -> Result<Box<dyn Any>> {
+        let mut deserializer = JsonDeserializer::from_slice(bytes);
+        let data = data_decoder.decode(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(data)
     }
 
     #[inline]
-    pub(super) fn encode_data<D: Serialize>(&self, data: &D) -> Result<Vec<u8>> {
-        Ok(to_vec(data)?)
+    fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>> {
+        Ok(to_vec(&InnerPacketRef {
+            ack_id: packet.ack_id,
+            data: packet.data.as_deref().map(from_slice).transpose()?,
+            key: packet.key.as_deref(),
+            message: packet.message.as_deref(),
+            stream_id: packet.stream_id,
+            r#type: &packet.r#type,
+            version: packet.version,
+        })?)
     }
+
+    #[inline]
+    fn encode_data(&self, data: &ErasedPacketData) -> Result<Vec<u8>> {
+        self.encode_data(data)
+    }
+
+    #[inline]
+    fn is_text(&self) -> bool {
+        Self::IS_TEXT
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+// Functions
+/// Keeps an explicit `null` payload as `Some` so it is still dispatched to handlers.
+#[inline]
+fn deserialize_raw_data<'a, 'de: 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Option<&'a RawValue>, D::Error> {
+    Ok(Some(<&RawValue>::deserialize(deserializer)?))
 }
@@ -1,23 +1,60 @@
+use std::borrow::Cow;
+
+use bytes::Bytes;
+use num_enum::{
+    FromPrimitive,
+    IntoPrimitive,
+};
 use serde::{
     Deserialize,
+    Deserializer,
     Serialize,
+    Serializer,
 };
-use serde_repr::{
-    Deserialize_repr,
-    Serialize_repr,
+use serde_with::{
+    BorrowCow,
+    Bytes as SerdeBytes,
+    serde_as,
+    skip_serializing_none,
 };
-use serde_with::skip_serializing_none;
 
 pub mod codecs;
 
 // Enums
 #[repr(u8)]
-#[derive(Clone, Debug, Deserialize_repr, Serialize_repr)]
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, IntoPrimitive, PartialEq)]
 pub enum WsIoPacketType {
     Disconnect = 0,
     Event = 1,
     Init = 2,
     Ready = 3,
+    Ack = 4,
+    Error = 5,
+    Ping = 6,
+    Pong = 7,
+    StreamOpen = 8,
+    StreamData = 9,
+    StreamCredit = 10,
+    StreamEnd = 11,
+    StreamCancel = 12,
+
+    /// Packet type introduced by a newer protocol version; ignored by the receiver.
+    #[num_enum(catch_all)]
+    Unknown(u8),
+}
+
+impl<'de> Deserialize<'de> for WsIoPacketType {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from(u8::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for WsIoPacketType {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*self).into())
+    }
 }
 
 // Structs
@@ -26,58 +63,329 @@ pub enum WsIoPacketType {
     feature = "packet-codec-msgpack",
     feature = "packet-codec-postcard"
 ))]
+/// Rest of the envelope of binary codecs, following its header.
+///
+/// Its layout may change with the protocol version, in which case peers speaking an older version are rejected since
+/// only the current layout is understood.
+#[serde_as]
 #[derive(Deserialize)]
-struct InnerPacket(Option<Vec<u8>>, Option<String>, WsIoPacketType);
+struct InnerPacketBody<'a>(
+    Option<u32>,
+    #[serde(borrow)]
+    #[serde_as(as = "Vec<SerdeBytes>")]
+    Vec<Cow<'a, [u8]>>,
+    #[serde(borrow)]
+    #[serde_as(as = "Option<SerdeBytes>", no_default)]
+    Option<Cow<'a, [u8]>>,
+    #[serde(borrow)]
+    #[serde_as(as = "Option<BorrowCow>", no_default)]
+    Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    #[serde_as(as = "Option<BorrowCow>", no_default)]
+    Option<Cow<'a, str>>,
+    Option<u32>,
+);
 
 #[cfg(any(
     feature = "packet-codec-bincode",
     feature = "packet-codec-msgpack",
     feature = "packet-codec-postcard"
 ))]
+#[serde_as]
 #[derive(Serialize)]
-struct InnerPacketRef<'a>(&'a Option<Vec<u8>>, &'a Option<String>, &'a WsIoPacketType);
+struct InnerPacketBodyRef<'a>(
+    &'a Option<u32>,
+    #[serde_as(as = "&Vec<SerdeBytes>")] &'a Vec<Cow<'a, [u8]>>,
+    #[serde_as(as = "&Option<SerdeBytes>")] &'a Option<Cow<'a, [u8]>>,
+    &'a Option<Cow<'a, str>>,
+    &'a Option<Cow<'a, str>>,
+    &'a Option<u32>,
+);
 
+#[cfg(any(
+    feature = "packet-codec-bincode",
+    feature = "packet-codec-msgpack",
+    feature = "packet-codec-postcard"
+))]
+/// Leading part of the envelope of binary codecs, holding the packet type and the protocol version declared by init
+/// packets.
+///
+/// Its layout must never change, so that peers speaking another protocol version can still read their init packets
+/// and negotiate, even if they cannot decode the rest of the envelope.
+#[derive(Deserialize)]
+struct InnerPacketHeader(WsIoPacketType, Option<u16>);
+
+#[cfg(any(
+    feature = "packet-codec-bincode",
+    feature = "packet-codec-msgpack",
+    feature = "packet-codec-postcard"
+))]
+#[derive(Serialize)]
+struct InnerPacketHeaderRef<'a>(&'a WsIoPacketType, &'a Option<u16>);
+
+/// Packet envelope, borrowing its fields from the received frame when the codec allows it.
+#[serde_as]
 #[skip_serializing_none]
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct WsIoPacket {
-    #[serde(rename = "d")]
-    pub data: Option<Vec<u8>>,
+pub struct WsIoPacket<'a> {
+    #[serde(rename = "a")]
+    pub ack_id: Option<u32>,
+
+    /// Binary blobs sent alongside the payload. Binary codecs carry them as native bytes values, text codecs frame
+    /// them around the envelope, see [`WsIoPacketCodec::encode`](codecs::WsIoPacketCodec::encode).
+    #[serde(borrow, default, rename = "b", skip_serializing_if = "Vec::is_empty")]
+    #[serde_as(as = "Vec<SerdeBytes>")]
+    pub attachments: Vec<Cow<'a, [u8]>>,
+
+    /// Encoded event payload. Binary codecs carry it as a native bytes value, text codecs embed it as raw JSON.
+    #[serde(borrow, rename = "d")]
+    #[serde_as(as = "Option<SerdeBytes>")]
+    pub data: Option<Cow<'a, [u8]>>,
 
-    #[serde(rename = "k")]
-    pub key: Option<String>,
+    #[serde(borrow, rename = "k")]
+    #[serde_as(as = "Option<BorrowCow>")]
+    pub key: Option<Cow<'a, str>>,
+
+    /// Error message, only carried by error packets and by stream packets ending or cancelling a stream.
+    #[serde(borrow, rename = "m")]
+    #[serde_as(as = "Option<BorrowCow>")]
+    pub message: Option<Cow<'a, str>>,
+
+    /// Stream the packet belongs to, only carried by stream packets.
+    #[serde(rename = "s")]
+    pub stream_id: Option<u32>,
 
     #[serde(rename = "t")]
     pub r#type: WsIoPacketType,
+
+    /// Protocol version declared by the sender, only carried by init packets.
+    #[serde(rename = "v")]
+    pub version: Option<u16>,
 }
 
-impl WsIoPacket {
+impl<'a> WsIoPacket<'a> {
     #[inline]
-    pub fn new(r#type: WsIoPacketType, key: Option<&str>, data: Option<Vec<u8>>) -> Self {
+    pub fn new(r#type: WsIoPacketType, key: Option<&'a str>, data: Option<Vec<u8>>) -> Self {
         Self {
-            data,
-            key: key.map(|k| k.into()),
+            ack_id: None,
+            attachments: Vec::new(),
+            data: data.map(Cow::Owned),
+            key: key.map(Cow::Borrowed),
+            message: None,
+            stream_id: None,
             r#type,
+            version: None,
         }
     }
 
+    // Private methods
+    #[cfg(any(
+        feature = "packet-codec-bincode",
+        feature = "packet-codec-msgpack",
+        feature = "packet-codec-postcard"
+    ))]
+    #[inline]
+    fn body_ref(&'a self) -> InnerPacketBodyRef<'a> {
+        InnerPacketBodyRef(
+            &self.ack_id,
+            &self.attachments,
+            &self.data,
+            &self.key,
+            &self.message,
+            &self.stream_id,
+        )
+    }
+
+    /// Assembles a packet decoded by a binary codec.
+    ///
+    /// Init packets whose body cannot be decoded are kept with their declared version only, as they come from a peer
+    /// speaking another protocol version that must be told so by the version negotiation.
+    #[cfg(any(
+        feature = "packet-codec-bincode",
+        feature = "packet-codec-msgpack",
+        feature = "packet-codec-postcard"
+    ))]
+    fn from_header_and_body<E: Into<anyhow::Error>>(
+        header: InnerPacketHeader,
+        body: Result<InnerPacketBody<'a>, E>,
+    ) -> anyhow::Result<Self> {
+        let InnerPacketHeader(r#type, version) = header;
+        let body = match body {
+            Ok(body) => body,
+            Err(_) if r#type == WsIoPacketType::Init => InnerPacketBody(None, Vec::new(), None, None, None, None),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            ack_id: body.0,
+            attachments: body.1,
+            data: body.2,
+            key: body.3,
+            message: body.4,
+            stream_id: body.5,
+            r#type,
+            version,
+        })
+    }
+
+    #[cfg(any(
+        feature = "packet-codec-bincode",
+        feature = "packet-codec-msgpack",
+        feature = "packet-codec-postcard"
+    ))]
+    #[inline]
+    fn header_ref(&'a self) -> InnerPacketHeaderRef<'a> {
+        InnerPacketHeaderRef(&self.r#type, &self.version)
+    }
+
     // Public methods
+    #[inline]
+    pub fn new_ack(ack_id: u32, data: Option<Vec<u8>>) -> Self {
+        Self {
+            ack_id: Some(ack_id),
+            ..Self::new(WsIoPacketType::Ack, None, data)
+        }
+    }
+
     #[inline]
     pub fn new_disconnect() -> Self {
         Self::new(WsIoPacketType::Disconnect, None, None)
     }
 
     #[inline]
-    pub fn new_event(event: &str, data: Option<Vec<u8>>) -> Self {
+    pub fn new_error(code: &'a str, message: &'a str, data: Option<Vec<u8>>) -> Self {
+        Self {
+            message: Some(Cow::Borrowed(message)),
+            ..Self::new(WsIoPacketType::Error, Some(code), data)
+        }
+    }
+
+    #[inline]
+    pub fn new_event(event: &'a str, data: Option<Vec<u8>>) -> Self {
         Self::new(WsIoPacketType::Event, Some(event), data)
     }
 
+    /// Builds an event packet carrying `attachments` as separate binary blobs next to its payload.
     #[inline]
-    pub fn new_init(data: Option<Vec<u8>>) -> Self {
-        Self::new(WsIoPacketType::Init, None, data)
+    pub fn new_event_with_attachments(event: &'a str, data: Option<Vec<u8>>, attachments: Vec<&'a [u8]>) -> Self {
+        Self {
+            attachments: attachments.into_iter().map(Cow::Borrowed).collect(),
+            ..Self::new_event(event, data)
+        }
+    }
+
+    #[inline]
+    pub fn new_event_with_ack(event: &'a str, ack_id: u32, data: Option<Vec<u8>>) -> Self {
+        Self {
+            ack_id: Some(ack_id),
+            ..Self::new_event(event, data)
+        }
+    }
+
+    #[inline]
+    pub fn new_init(version: u16, data: Option<Vec<u8>>) -> Self {
+        Self {
+            version: Some(version),
+            ..Self::new(WsIoPacketType::Init, None, data)
+        }
+    }
+
+    #[inline]
+    pub fn new_ping() -> Self {
+        Self::new(WsIoPacketType::Ping, None, None)
+    }
+
+    #[inline]
+    pub fn new_pong() -> Self {
+        Self::new(WsIoPacketType::Pong, None, None)
+    }
+
+    /// Builds an event packet around a payload that is already encoded, without copying it.
+    #[inline]
+    pub fn new_raw_event(event: &'a str, data: Option<&'a [u8]>) -> Self {
+        Self {
+            data: data.map(Cow::Borrowed),
+            ..Self::new_event(event, None)
+        }
     }
 
     #[inline]
     pub fn new_ready() -> Self {
         Self::new(WsIoPacketType::Ready, None, None)
     }
+
+    #[inline]
+    pub fn new_stream_cancel(stream_id: u32, message: &'a str) -> Self {
+        Self {
+            message: Some(Cow::Borrowed(message)),
+            stream_id: Some(stream_id),
+            ..Self::new(WsIoPacketType::StreamCancel, None, None)
+        }
+    }
+
+    #[inline]
+    pub fn new_stream_credit(stream_id: u32, data: Vec<u8>) -> Self {
+        Self {
+            stream_id: Some(stream_id),
+            ..Self::new(WsIoPacketType::StreamCredit, None, Some(data))
+        }
+    }
+
+    /// Builds a stream packet carrying `chunk` as an attachment, so it is sent as is by every codec.
+    #[inline]
+    pub fn new_stream_data(stream_id: u32, chunk: &'a [u8]) -> Self {
+        Self {
+            attachments: vec![Cow::Borrowed(chunk)],
+            stream_id: Some(stream_id),
+            ..Self::new(WsIoPacketType::StreamData, None, None)
+        }
+    }
+
+    /// Builds the packet ending a stream, failed with `message` if given.
+    #[inline]
+    pub fn new_stream_end(stream_id: u32, message: Option<&'a str>) -> Self {
+        Self {
+            message: message.map(Cow::Borrowed),
+            stream_id: Some(stream_id),
+            ..Self::new(WsIoPacketType::StreamEnd, None, None)
+        }
+    }
+
+    #[inline]
+    pub fn new_stream_open(stream_id: u32, stream: &'a str, data: Option<Vec<u8>>) -> Self {
+        Self {
+            stream_id: Some(stream_id),
+            ..Self::new(WsIoPacketType::StreamOpen, Some(stream), data)
+        }
+    }
+
+    /// Takes the attachments of a packet decoded from `frame`, sharing the frame buffer like
+    /// [`take_frame_data`](Self::take_frame_data).
+    pub fn take_frame_attachments(&mut self, frame: &Bytes) -> Vec<Bytes> {
+        self.attachments
+            .drain(..)
+            .map(|attachment| frame_bytes(frame, attachment))
+            .collect()
+    }
+
+    /// Takes the payload of a packet decoded from `frame`, sharing the frame buffer instead of copying it when the
+    /// codec borrowed the payload from there.
+    #[inline]
+    pub fn take_frame_data(&mut self, frame: &Bytes) -> Option<Bytes> {
+        Some(frame_bytes(frame, self.data.take()?))
+    }
+}
+
+// Functions
+#[inline]
+fn frame_bytes(frame: &Bytes, bytes: Cow<[u8]>) -> Bytes {
+    match bytes {
+        Cow::Borrowed(bytes)
+            if frame.as_ptr_range().start <= bytes.as_ptr() && bytes.as_ptr_range().end <= frame.as_ptr_range().end =>
+        {
+            frame.slice_ref(bytes)
+        }
+        Cow::Borrowed(bytes) => Bytes::copy_from_slice(bytes),
+        Cow::Owned(bytes) => Bytes::from(bytes),
+    }
 }
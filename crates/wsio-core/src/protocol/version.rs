@@ -0,0 +1,58 @@
+use anyhow::{
+    Result,
+    bail,
+};
+
+// Functions
+/// Negotiates the protocol version to speak with a peer that declared `peer_version` in its init packet.
+///
+/// Newer peers are downgraded to [`WS_IO_PROTOCOL_VERSION`], older peers or peers without a declared version are
+/// rejected.
+pub fn negotiate_protocol_version(peer_version: Option<u16>) -> Result<u16> {
+    let Some(peer_version) = peer_version else {
+        bail!("Peer did not declare a protocol version, supported version is {WS_IO_PROTOCOL_VERSION}");
+    };
+
+    if peer_version < WS_IO_PROTOCOL_VERSION {
+        bail!("Unsupported protocol version {peer_version}, supported version is {WS_IO_PROTOCOL_VERSION}");
+    }
+
+    Ok(WS_IO_PROTOCOL_VERSION)
+}
+
+// Constants/Statics
+/// Protocol version spoken by this build, declared in init packets.
+///
+/// Must be bumped along with any change to the wire format, such as a new packet type or envelope field.
+pub const WS_IO_PROTOCOL_VERSION: u16 = 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downgrades_newer_peers() {
+        assert_eq!(
+            negotiate_protocol_version(Some(WS_IO_PROTOCOL_VERSION + 1)).unwrap(),
+            WS_IO_PROTOCOL_VERSION
+        );
+        assert_eq!(
+            negotiate_protocol_version(Some(u16::MAX)).unwrap(),
+            WS_IO_PROTOCOL_VERSION
+        );
+    }
+
+    #[test]
+    fn keeps_the_current_version() {
+        assert_eq!(
+            negotiate_protocol_version(Some(WS_IO_PROTOCOL_VERSION)).unwrap(),
+            WS_IO_PROTOCOL_VERSION
+        );
+    }
+
+    #[test]
+    fn rejects_older_peers_and_peers_without_a_version() {
+        assert!(negotiate_protocol_version(Some(WS_IO_PROTOCOL_VERSION - 1)).is_err());
+        assert!(negotiate_protocol_version(None).is_err());
+    }
+}
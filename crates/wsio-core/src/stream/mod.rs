@@ -0,0 +1,3 @@
+pub mod receiver;
+pub mod registry;
+pub mod sender;
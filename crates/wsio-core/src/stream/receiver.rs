@@ -0,0 +1,134 @@
+use std::{
+    mem::take,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{
+            AtomicU32,
+            Ordering,
+        },
+    },
+    task::{
+        Context,
+        Poll,
+        ready,
+    },
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures_core::Stream;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{
+    packet::WsIoPacket,
+    traits::stream::sender::StreamPacketSender,
+};
+
+// Structs
+/// Receiving half of a stream opened by the peer, yielding its chunks in order.
+///
+/// More credit is granted to the sender as chunks are consumed, so the sender waits while they are not. The stream
+/// yields an error if the sender aborts it or the connection closes, dropping the receiver before the end cancels it.
+pub struct WsIoStreamReceiver<C: StreamPacketSender> {
+    chunk_rx: UnboundedReceiver<Result<Bytes>>,
+    consumed_chunks: u32,
+    credit_window: u32,
+    ctx: Arc<C>,
+    ended: bool,
+    remaining_credit: Arc<AtomicU32>,
+    stream_id: u32,
+}
+
+impl<C: StreamPacketSender> Drop for WsIoStreamReceiver<C> {
+    fn drop(&mut self) {
+        // Streams of a closed connection were already failed by the registry
+        if self.ended || self.ctx.cancel_token().is_cancelled() {
+            return;
+        }
+
+        self.ctx.stream_registry().remove_incoming_stream(self.stream_id);
+        let ctx = self.ctx.clone();
+        let stream_id = self.stream_id;
+        self.ctx.spawn_task(async move {
+            ctx.send_stream_packet(&WsIoPacket::new_stream_cancel(
+                stream_id,
+                "Stream receiver dropped before the end",
+            ))
+            .await
+        });
+    }
+}
+
+impl<C: StreamPacketSender> Stream for WsIoStreamReceiver<C> {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = ready!(self.chunk_rx.poll_recv(cx));
+        match chunk {
+            Some(Ok(_)) => {
+                // Grant credit in batches of half the window so the sender rarely has to wait for it
+                self.consumed_chunks += 1;
+                if self.consumed_chunks >= (self.credit_window / 2).max(1) {
+                    self.grant_credit();
+                }
+            }
+            _ => self.ended = true,
+        }
+
+        Poll::Ready(chunk)
+    }
+}
+
+impl<C: StreamPacketSender> WsIoStreamReceiver<C> {
+    #[inline]
+    pub(crate) fn new(
+        ctx: Arc<C>,
+        stream_id: u32,
+        chunk_rx: UnboundedReceiver<Result<Bytes>>,
+        remaining_credit: Arc<AtomicU32>,
+        credit_window: u32,
+    ) -> Self {
+        Self {
+            chunk_rx,
+            consumed_chunks: 0,
+            credit_window,
+            ctx,
+            ended: false,
+            remaining_credit,
+            stream_id,
+        }
+    }
+
+    // Private methods
+    fn grant_credit(&mut self) {
+        let credit = take(&mut self.consumed_chunks);
+        self.remaining_credit.fetch_add(credit, Ordering::AcqRel);
+
+        let ctx = self.ctx.clone();
+        let stream_id = self.stream_id;
+        self.ctx.spawn_task(async move {
+            let credit_data = ctx.packet_codec().encode_data(&credit)?;
+            ctx.send_stream_packet(&WsIoPacket::new_stream_credit(stream_id, credit_data))
+                .await
+        });
+    }
+
+    // Public methods
+    /// Cancels the stream, making the sender fail to send further chunks.
+    pub async fn cancel(mut self) -> Result<()> {
+        self.ended = true;
+        self.ctx.stream_registry().remove_incoming_stream(self.stream_id);
+        self.ctx
+            .send_stream_packet(&WsIoPacket::new_stream_cancel(
+                self.stream_id,
+                "Stream cancelled by the receiver",
+            ))
+            .await
+    }
+
+    #[inline]
+    pub fn stream_id(&self) -> u32 {
+        self.stream_id
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    any::Any,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{
+            AtomicU32,
+            AtomicU64,
+            Ordering,
+        },
+    },
+};
+
+use anyhow::{
+    Result,
+    anyhow,
+    bail,
+};
+use bytes::Bytes;
+use parking_lot::{
+    Mutex,
+    RwLock,
+};
+use serde::de::DeserializeOwned;
+use tokio::sync::{
+    Semaphore,
+    mpsc::{
+        UnboundedSender,
+        unbounded_channel,
+    },
+};
+
+use super::{
+    receiver::WsIoStreamReceiver,
+    sender::WsIoStreamSender,
+};
+use crate::{
+    event::subscription::WsIoSubscription,
+    packet::{
+        WsIoPacket,
+        WsIoPacketType,
+        codecs::WsIoPacketCodec,
+    },
+    traits::stream::sender::StreamPacketSender,
+    types::hashers::{
+        FxDashMap,
+        FxHashMap,
+    },
+};
+
+// Types
+type StreamHandler<C> = Arc<
+    dyn Fn(Arc<C>, Option<Bytes>, WsIoStreamReceiver<C>) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+type StreamHandlers<C> = Arc<RwLock<FxHashMap<String, (u64, StreamHandler<C>)>>>;
+
+// Structs
+/// Stream opened by the peer, whose chunks are forwarded to its receiver.
+struct IncomingStream {
+    chunk_tx: UnboundedSender<Result<Bytes>>,
+
+    /// Chunks the peer may still send before it is granted more credit, shared with the receiver.
+    remaining_credit: Arc<AtomicU32>,
+}
+
+/// Stream opened by this side, shared with its sender.
+pub(crate) struct OutgoingStream {
+    cancel_message: Mutex<Option<String>>,
+    credit: Semaphore,
+}
+
+impl OutgoingStream {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            cancel_message: Mutex::new(None),
+            credit: Semaphore::new(0),
+        }
+    }
+
+    // Private methods
+    #[inline]
+    fn cancel(&self, message: &str) {
+        *self.cancel_message.lock() = Some(message.into());
+        self.credit.close();
+    }
+
+    // Protected methods
+    /// Waits until the receiver grants credit for one more chunk and consumes it.
+    pub(crate) async fn acquire_credit(&self) -> Result<()> {
+        match self.credit.acquire().await {
+            Ok(permit) => {
+                permit.forget();
+                Ok(())
+            }
+            Err(_) => bail!(
+                "{}",
+                self.cancel_message.lock().as_deref().unwrap_or("Stream cancelled")
+            ),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.credit.is_closed()
+    }
+}
+
+pub struct WsIoStreamRegistry<C: StreamPacketSender> {
+    credit_window: u32,
+    handlers: StreamHandlers<C>,
+    incoming_streams: FxDashMap<u32, IncomingStream>,
+    next_handler_id: AtomicU64,
+    next_stream_id: AtomicU32,
+    outgoing_streams: FxDashMap<u32, Arc<OutgoingStream>>,
+}
+
+impl<C: StreamPacketSender> WsIoStreamRegistry<C> {
+    /// Creates a registry granting the peer credit for `credit_window` chunks of each stream it opens.
+    #[inline]
+    pub fn new(credit_window: u32) -> Self {
+        Self {
+            credit_window: credit_window.max(1),
+            handlers: Arc::new(RwLock::new(FxHashMap::default())),
+            incoming_streams: FxDashMap::default(),
+            next_handler_id: AtomicU64::new(0),
+            next_stream_id: AtomicU32::new(0),
+            outgoing_streams: FxDashMap::default(),
+        }
+    }
+
+    // Private methods
+    async fn handle_open_packet(
+        &self,
+        ctx: &Arc<C>,
+        stream_id: u32,
+        stream: &str,
+        packet_data: Option<Bytes>,
+    ) -> Result<()> {
+        let handler = self.handlers.read().get(stream).map(|(_, handler)| handler.clone());
+        let Some(handler) = handler else {
+            let message = format!("No handler for stream '{stream}'");
+            return ctx
+                .send_stream_packet(&WsIoPacket::new_stream_cancel(stream_id, &message))
+                .await;
+        };
+
+        let (chunk_tx, chunk_rx) = unbounded_channel();
+        let remaining_credit = Arc::new(AtomicU32::new(self.credit_window));
+        self.incoming_streams.insert(
+            stream_id,
+            IncomingStream {
+                chunk_tx,
+                remaining_credit: remaining_credit.clone(),
+            },
+        );
+
+        let receiver = WsIoStreamReceiver::new(ctx.clone(), stream_id, chunk_rx, remaining_credit, self.credit_window);
+        let credit_data = ctx.packet_codec().encode_data(&self.credit_window)?;
+        ctx.send_stream_packet(&WsIoPacket::new_stream_credit(stream_id, credit_data))
+            .await?;
+
+        // The handler owns the receiver, dropping it early cancels the stream
+        let ctx_clone = ctx.clone();
+        let stream = stream.to_owned();
+        ctx.spawn_task(async move {
+            if let Err(err) = handler(ctx_clone.clone(), packet_data, receiver).await {
+                ctx_clone.report_event_error(&stream, err).await;
+            }
+
+            Ok(())
+        });
+
+        Ok(())
+    }
+
+    // Protected methods
+    #[inline]
+    pub(crate) fn remove_incoming_stream(&self, stream_id: u32) {
+        self.incoming_streams.remove(&stream_id);
+    }
+
+    #[inline]
+    pub(crate) fn remove_outgoing_stream(&self, stream_id: u32) {
+        self.outgoing_streams.remove(&stream_id);
+    }
+
+    // Public methods
+    /// Fails every open stream, called once the connection is closed.
+    pub fn clear(&self) {
+        self.incoming_streams.retain(|_, incoming_stream| {
+            let _ = incoming_stream.chunk_tx.send(Err(anyhow!("Connection closed")));
+            false
+        });
+
+        self.outgoing_streams.retain(|_, outgoing_stream| {
+            outgoing_stream.cancel("Connection closed");
+            false
+        });
+    }
+
+    pub async fn handle_packet(&self, ctx: &Arc<C>, mut packet: WsIoPacket<'_>, frame: &Bytes) -> Result<()> {
+        let Some(stream_id) = packet.stream_id else {
+            bail!("Stream packet missing stream id");
+        };
+
+        match packet.r#type {
+            WsIoPacketType::StreamCancel => {
+                if let Some((_, outgoing_stream)) = self.outgoing_streams.remove(&stream_id) {
+                    outgoing_stream.cancel(packet.message.as_deref().unwrap_or("Stream cancelled by the receiver"));
+                }
+            }
+            WsIoPacketType::StreamCredit => {
+                let Some(packet_data) = packet.data.as_deref() else {
+                    bail!("Stream credit packet missing data");
+                };
+
+                let credit = ctx.packet_codec().decode_data::<u32>(packet_data)?;
+                if let Some(outgoing_stream) = self.outgoing_streams.get(&stream_id) {
+                    outgoing_stream.credit.add_permits(credit as usize);
+                }
+            }
+            WsIoPacketType::StreamData => {
+                let Some(chunk) = packet.take_frame_attachments(frame).into_iter().next() else {
+                    bail!("Stream data packet missing attachment");
+                };
+
+                // Chunks sent before the peer learned that the stream was cancelled are dropped
+                let Some((chunk_tx, remaining_credit)) = self.incoming_streams.get(&stream_id).map(|incoming_stream| {
+                    (
+                        incoming_stream.chunk_tx.clone(),
+                        incoming_stream.remaining_credit.clone(),
+                    )
+                }) else {
+                    return Ok(());
+                };
+
+                // Cancel streams whose sender ignores its credit instead of buffering their chunks without bound
+                if remaining_credit
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |credit| credit.checked_sub(1))
+                    .is_err()
+                {
+                    let message = "Stream sender exceeded its credit";
+                    self.incoming_streams.remove(&stream_id);
+                    let _ = chunk_tx.send(Err(anyhow!(message)));
+
+                    return ctx
+                        .send_stream_packet(&WsIoPacket::new_stream_cancel(stream_id, message))
+                        .await;
+                }
+
+                let _ = chunk_tx.send(Ok(chunk));
+            }
+            WsIoPacketType::StreamEnd => {
+                // Dropping the chunk sender ends the receiver once the buffered chunks are consumed
+                if let Some((_, incoming_stream)) = self.incoming_streams.remove(&stream_id)
+                    && let Some(message) = packet.message
+                {
+                    let _ = incoming_stream.chunk_tx.send(Err(anyhow!("{message}")));
+                }
+            }
+            WsIoPacketType::StreamOpen => {
+                let packet_data = packet.take_frame_data(frame);
+                let Some(stream) = packet.key.as_deref() else {
+                    bail!("Stream open packet missing key");
+                };
+
+                self.handle_open_packet(ctx, stream_id, stream, packet_data).await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn off(&self, stream: &str) {
+        self.handlers.write().remove(stream);
+    }
+
+    /// Registers the handler of the streams named `stream` opened by the peer, replacing any previous one.
+    ///
+    /// The handler receives the data sent when the stream was opened and the receiver yielding its chunks.
+    pub fn on<H, Fut, D>(&self, stream: &str, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<C>, Arc<D>, WsIoStreamReceiver<C>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+    {
+        let handler: StreamHandler<C> = Arc::new(move |ctx, packet_data, receiver| {
+            match decode_stream_data::<D>(packet_data.as_ref(), ctx.packet_codec()) {
+                Ok(data) => Box::pin(handler(ctx, data, receiver)),
+                Err(err) => Box::pin(async move { Err(err.context("Failed to decode stream data")) }),
+            }
+        });
+
+        let handler_id = self.next_handler_id.fetch_add(1, Ordering::Relaxed);
+        self.handlers.write().insert(stream.into(), (handler_id, handler));
+
+        let handlers = Arc::downgrade(&self.handlers);
+        let stream = stream.to_owned();
+        WsIoSubscription::new(handler_id, move || {
+            let Some(handlers) = handlers.upgrade() else {
+                return;
+            };
+
+            // Leave the handler alone if it was replaced since
+            let mut handlers = handlers.write();
+            if handlers
+                .get(&stream)
+                .is_some_and(|(current_handler_id, _)| *current_handler_id == handler_id)
+            {
+                handlers.remove(&stream);
+            }
+        })
+    }
+
+    /// Opens a stream named `stream` with the peer, sending `packet_data` along with the request.
+    pub async fn open(&self, ctx: &Arc<C>, stream: &str, packet_data: Option<Vec<u8>>) -> Result<WsIoStreamSender<C>> {
+        let stream_id = self.next_stream_id.fetch_add(1, Ordering::Relaxed);
+        let outgoing_stream = Arc::new(OutgoingStream::new());
+        self.outgoing_streams.insert(stream_id, outgoing_stream.clone());
+        if let Err(err) = ctx
+            .send_stream_packet(&WsIoPacket::new_stream_open(stream_id, stream, packet_data))
+            .await
+        {
+            self.outgoing_streams.remove(&stream_id);
+            return Err(err);
+        }
+
+        Ok(WsIoStreamSender::new(ctx.clone(), stream_id, outgoing_stream))
+    }
+}
+
+// Functions
+#[inline]
+fn decode_stream_data<D: DeserializeOwned + Send + Sync + 'static>(
+    packet_data: Option<&Bytes>,
+    packet_codec: &WsIoPacketCodec,
+) -> Result<Arc<D>> {
+    match packet_data {
+        Some(packet_data) => Ok(Arc::new(packet_codec.decode_data(packet_data)?)),
+        None => (Arc::new(()) as Arc<dyn Any + Send + Sync>)
+            .downcast()
+            .map_err(|_| anyhow!("Stream opened without data")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use futures_core::Stream;
+
+    use super::*;
+    use crate::testing::{
+        TestPeer,
+        is_pending,
+    };
+
+    // Functions
+    /// Credits granted so far by the sent stream credit packets, consuming every sent packet.
+    fn take_granted_credits(peer: &TestPeer) -> Vec<u32> {
+        peer.take_sent_packets()
+            .into_iter()
+            .filter(|sent_packet| sent_packet.r#type == WsIoPacketType::StreamCredit)
+            .map(|sent_packet| {
+                peer.packet_codec()
+                    .decode_data(sent_packet.data.as_deref().unwrap())
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn last_sent_packet_type(peer: &TestPeer) -> Option<WsIoPacketType> {
+        peer.take_sent_packets().last().map(|sent_packet| sent_packet.r#type)
+    }
+
+    async fn handle_packet(peer: &Arc<TestPeer>, packet: WsIoPacket<'_>) -> Result<()> {
+        peer.stream_registry.handle_packet(peer, packet, &Bytes::new()).await
+    }
+
+    async fn next_chunk(receiver: &mut WsIoStreamReceiver<TestPeer>) -> Option<Result<Bytes>> {
+        poll_fn(|cx| Pin::new(&mut *receiver).poll_next(cx)).await
+    }
+
+    async fn open_incoming_stream(peer: &Arc<TestPeer>) -> WsIoStreamReceiver<TestPeer> {
+        let (receiver_tx, mut receiver_rx) = unbounded_channel();
+        peer.stream_registry
+            .on("upload", move |_, _: Arc<()>, receiver| {
+                let _ = receiver_tx.send(receiver);
+                async { Ok(()) }
+            })
+            .detach();
+
+        handle_packet(peer, WsIoPacket::new_stream_open(0, "upload", None))
+            .await
+            .unwrap();
+
+        receiver_rx.recv().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn cancels_incoming_streams_exceeding_their_credit() {
+        let peer = TestPeer::with_credit_window(2);
+        let mut receiver = open_incoming_stream(&peer).await;
+        assert_eq!(take_granted_credits(&peer), [2]);
+
+        for _ in 0..3 {
+            handle_packet(&peer, WsIoPacket::new_stream_data(0, b"chunk"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(last_sent_packet_type(&peer), Some(WsIoPacketType::StreamCancel));
+        assert!(next_chunk(&mut receiver).await.unwrap().is_ok());
+        assert!(next_chunk(&mut receiver).await.unwrap().is_ok());
+        assert!(next_chunk(&mut receiver).await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn grants_credit_as_chunks_are_consumed() {
+        let peer = TestPeer::with_credit_window(4);
+        let mut receiver = open_incoming_stream(&peer).await;
+        assert_eq!(take_granted_credits(&peer), [4]);
+
+        for _ in 0..4 {
+            handle_packet(&peer, WsIoPacket::new_stream_data(0, b"chunk"))
+                .await
+                .unwrap();
+        }
+
+        // Credit is granted in batches of half the window
+        next_chunk(&mut receiver).await.unwrap().unwrap();
+        peer.wait_for_idle().await;
+        assert!(take_granted_credits(&peer).is_empty());
+
+        next_chunk(&mut receiver).await.unwrap().unwrap();
+        peer.wait_for_idle().await;
+        assert_eq!(take_granted_credits(&peer), [2]);
+
+        for _ in 0..2 {
+            handle_packet(&peer, WsIoPacket::new_stream_data(0, b"chunk"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(last_sent_packet_type(&peer), None);
+        handle_packet(&peer, WsIoPacket::new_stream_data(0, b"chunk"))
+            .await
+            .unwrap();
+        assert_eq!(last_sent_packet_type(&peer), Some(WsIoPacketType::StreamCancel));
+    }
+
+    #[tokio::test]
+    async fn rejects_stream_data_without_a_chunk() {
+        let peer = TestPeer::with_credit_window(2);
+        let _receiver = open_incoming_stream(&peer).await;
+        let mut packet = WsIoPacket::new_stream_data(0, b"chunk");
+        packet.attachments.clear();
+
+        assert!(handle_packet(&peer, packet).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_chunks_only_within_granted_credit() {
+        let peer = TestPeer::new();
+        let mut sender = peer.stream_registry.open(&peer, "upload", None).await.unwrap();
+        assert!(is_pending(sender.send(b"chunk")));
+
+        let credit_data = peer.packet_codec().encode_data(&2u32).unwrap();
+        handle_packet(&peer, WsIoPacket::new_stream_credit(0, credit_data))
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            sender.send(b"chunk").await.unwrap();
+        }
+
+        assert!(is_pending(sender.send(b"chunk")));
+
+        handle_packet(
+            &peer,
+            WsIoPacket::new_stream_cancel(0, "Stream cancelled by the receiver"),
+        )
+        .await
+        .unwrap();
+
+        assert!(sender.is_cancelled());
+        assert_eq!(
+            sender.send(b"chunk").await.unwrap_err().to_string(),
+            "Stream cancelled by the receiver"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use super::registry::OutgoingStream;
+use crate::{
+    packet::WsIoPacket,
+    traits::stream::sender::StreamPacketSender,
+};
+
+// Structs
+/// Sending half of a stream opened with the peer.
+///
+/// Chunks are only sent while the receiver has granted credit for them, so [`send`](Self::send) waits whenever the
+/// receiver falls behind. The stream is ended with [`finish`](Self::finish) or [`abort`](Self::abort), dropping the
+/// sender aborts it.
+pub struct WsIoStreamSender<C: StreamPacketSender> {
+    ctx: Arc<C>,
+    ended: bool,
+    outgoing_stream: Arc<OutgoingStream>,
+    stream_id: u32,
+}
+
+impl<C: StreamPacketSender> Drop for WsIoStreamSender<C> {
+    fn drop(&mut self) {
+        // Streams of a closed connection were already failed by the registry
+        if self.ended || self.ctx.cancel_token().is_cancelled() {
+            return;
+        }
+
+        self.ctx.stream_registry().remove_outgoing_stream(self.stream_id);
+        let ctx = self.ctx.clone();
+        let stream_id = self.stream_id;
+        self.ctx.spawn_task(async move {
+            ctx.send_stream_packet(&WsIoPacket::new_stream_end(
+                stream_id,
+                Some("Stream sender dropped before finishing"),
+            ))
+            .await
+        });
+    }
+}
+
+impl<C: StreamPacketSender> WsIoStreamSender<C> {
+    #[inline]
+    pub(crate) fn new(ctx: Arc<C>, stream_id: u32, outgoing_stream: Arc<OutgoingStream>) -> Self {
+        Self {
+            ctx,
+            ended: false,
+            outgoing_stream,
+            stream_id,
+        }
+    }
+
+    // Private methods
+    async fn end(&mut self, message: Option<&str>) -> Result<()> {
+        self.ended = true;
+        self.ctx.stream_registry().remove_outgoing_stream(self.stream_id);
+        self.ctx
+            .send_stream_packet(&WsIoPacket::new_stream_end(self.stream_id, message))
+            .await
+    }
+
+    // Public methods
+    /// Ends the stream with an error yielded by the receiver.
+    pub async fn abort(mut self, message: &str) -> Result<()> {
+        self.end(Some(message)).await
+    }
+
+    pub async fn finish(mut self) -> Result<()> {
+        self.end(None).await
+    }
+
+    /// Whether the receiver cancelled the stream, in which case every further chunk fails to send.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.outgoing_stream.is_cancelled()
+    }
+
+    /// Sends `chunk` once the receiver has granted credit for it.
+    pub async fn send(&mut self, chunk: impl AsRef<[u8]>) -> Result<()> {
+        self.outgoing_stream.acquire_credit().await?;
+        self.ctx
+            .send_stream_packet(&WsIoPacket::new_stream_data(self.stream_id, chunk.as_ref()))
+            .await
+    }
+
+    #[inline]
+    pub fn stream_id(&self) -> u32 {
+        self.stream_id
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+    },
+    task::{
+        Context,
+        Waker,
+    },
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use tokio::{
+    select,
+    spawn,
+    sync::Notify,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    event::registry::{
+        WsIoEventPayload,
+        WsIoEventRegistry,
+    },
+    packet::{
+        WsIoPacket,
+        WsIoPacketType,
+        codecs::WsIoPacketCodec,
+    },
+    stream::registry::WsIoStreamRegistry,
+    traits::{
+        ack::sender::AckSender,
+        error::{
+            reporter::EventErrorReporter,
+            sender::ErrorSender,
+        },
+        stream::sender::StreamPacketSender,
+        task::spawner::TaskSpawner,
+    },
+};
+
+// Structs
+/// Decrements the running task count of a [`TestPeer`] when its task completes or is cancelled.
+struct RunningTask(Arc<RunningTasks>);
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_one();
+        }
+    }
+}
+
+#[derive(Default)]
+struct RunningTasks {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+/// Packet sent by a [`TestPeer`], with its data and error message.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct SentPacket {
+    pub(crate) ack_id: Option<u32>,
+    pub(crate) data: Option<Vec<u8>>,
+    pub(crate) message: Option<String>,
+    pub(crate) r#type: WsIoPacketType,
+}
+
+/// Peer recording the packets it sends, whose spawned tasks can be awaited with
+/// [`TestPeer::wait_for_idle`].
+pub(crate) struct TestPeer {
+    cancel_token: Arc<CancellationToken>,
+    packet_codec: WsIoPacketCodec,
+    running_tasks: Arc<RunningTasks>,
+    sent_packets: Mutex<Vec<SentPacket>>,
+    pub(crate) stream_registry: WsIoStreamRegistry<TestPeer>,
+}
+
+impl AckSender for TestPeer {
+    async fn send_ack(&self, ack_id: u32, data: Option<Vec<u8>>) -> Result<()> {
+        self.sent_packets.lock().push(SentPacket {
+            ack_id: Some(ack_id),
+            data,
+            message: None,
+            r#type: WsIoPacketType::Ack,
+        });
+
+        Ok(())
+    }
+}
+
+impl ErrorSender for TestPeer {
+    async fn send_error(&self, error: &anyhow::Error, ack_id: Option<u32>) -> Result<()> {
+        self.sent_packets.lock().push(SentPacket {
+            ack_id,
+            data: None,
+            message: Some(format!("{error:#}")),
+            r#type: WsIoPacketType::Error,
+        });
+
+        Ok(())
+    }
+}
+
+impl EventErrorReporter for TestPeer {
+    fn clear_event_errors(&self) {}
+
+    async fn report_event_error(self: &Arc<Self>, _: &str, _: anyhow::Error) {}
+}
+
+impl StreamPacketSender for TestPeer {
+    fn packet_codec(&self) -> &WsIoPacketCodec {
+        &self.packet_codec
+    }
+
+    async fn send_stream_packet(&self, packet: &WsIoPacket<'_>) -> Result<()> {
+        self.sent_packets.lock().push(SentPacket {
+            ack_id: packet.ack_id,
+            data: packet.data.as_deref().map(<[u8]>::to_vec),
+            message: packet.message.as_deref().map(str::to_owned),
+            r#type: packet.r#type,
+        });
+
+        Ok(())
+    }
+
+    fn stream_registry(&self) -> &WsIoStreamRegistry<Self> {
+        &self.stream_registry
+    }
+}
+
+impl TaskSpawner for TestPeer {
+    fn cancel_token(&self) -> Arc<CancellationToken> {
+        self.cancel_token.clone()
+    }
+
+    fn spawn_task<F: Future<Output = Result<()>> + Send + 'static>(&self, future: F) {
+        self.running_tasks.count.fetch_add(1, Ordering::AcqRel);
+        let running_task = RunningTask(self.running_tasks.clone());
+        let cancel_token = self.cancel_token.clone();
+        spawn(async move {
+            let _running_task = running_task;
+            select! {
+                _ = cancel_token.cancelled() => {},
+                _ = future => {},
+            }
+        });
+    }
+}
+
+impl TestPeer {
+    #[inline]
+    pub(crate) fn new() -> Arc<Self> {
+        Self::with_credit_window(16)
+    }
+
+    pub(crate) fn with_credit_window(credit_window: u32) -> Arc<Self> {
+        Arc::new(Self {
+            cancel_token: Arc::new(CancellationToken::new()),
+            packet_codec: WsIoPacketCodec::SerdeJson,
+            running_tasks: Arc::default(),
+            sent_packets: Mutex::new(Vec::new()),
+            stream_registry: WsIoStreamRegistry::new(credit_window),
+        })
+    }
+
+    /// Dispatches `event` with `data` encoded as JSON, without waiting for its handlers.
+    pub(crate) async fn dispatch(
+        self: &Arc<Self>,
+        event_registry: &WsIoEventRegistry<Self, Self>,
+        event: &str,
+        data: Option<&'static str>,
+        ack_id: Option<u32>,
+    ) -> Result<()> {
+        let payload = WsIoEventPayload {
+            attachments: Vec::new(),
+            data: data.map(|data| Bytes::from_static(data.as_bytes())),
+        };
+
+        event_registry
+            .dispatch_event_packet(self.clone(), event, &self.packet_codec, payload, ack_id, self)
+            .await
+    }
+
+    /// Takes the packets sent so far, in the order they were sent.
+    #[inline]
+    pub(crate) fn take_sent_packets(&self) -> Vec<SentPacket> {
+        self.sent_packets.lock().drain(..).collect()
+    }
+
+    /// Waits until every task spawned through this peer has completed.
+    pub(crate) async fn wait_for_idle(&self) {
+        while self.running_tasks.count.load(Ordering::Acquire) != 0 {
+            self.running_tasks.idle.notified().await;
+        }
+    }
+}
+
+// Functions
+/// Polls `future` once, returning whether it is still pending.
+pub(crate) fn is_pending(future: impl Future) -> bool {
+    pin!(future).poll(&mut Context::from_waker(Waker::noop())).is_pending()
+}
@@ -0,0 +1,5 @@
+use anyhow::Result;
+
+pub trait AckSender: Send + Sync + 'static {
+    fn send_ack(&self, ack_id: u32, data: Option<Vec<u8>>) -> impl Future<Output = Result<()>> + Send;
+}
@@ -0,0 +1,2 @@
+pub mod reporter;
+pub mod sender;
@@ -0,0 +1,9 @@
+use std::sync::Arc;
+
+pub trait EventErrorReporter: Send + Sync + 'static {
+    /// Called after an event handler succeeds, resetting the consecutive failure count.
+    fn clear_event_errors(&self);
+
+    /// Called when the payload of `event` fails to decode or one of its handlers fails.
+    fn report_event_error(self: &Arc<Self>, event: &str, error: anyhow::Error) -> impl Future<Output = ()> + Send;
+}
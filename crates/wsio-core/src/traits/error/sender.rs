@@ -0,0 +1,5 @@
+use anyhow::Result;
+
+pub trait ErrorSender: Send + Sync + 'static {
+    fn send_error(&self, error: &anyhow::Error, ack_id: Option<u32>) -> impl Future<Output = Result<()>> + Send;
+}
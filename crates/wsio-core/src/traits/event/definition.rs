@@ -0,0 +1,13 @@
+use serde::{
+    Serialize,
+    de::DeserializeOwned,
+};
+#[cfg(feature = "derive")]
+pub use wsio_macros::WsIoEvent;
+
+/// Event bound to a fixed name, whose payload is the implementing type.
+///
+/// Sharing these types between client and server checks event names and payload types at compile time.
+pub trait WsIoEvent: DeserializeOwned + Serialize + Send + Sync + 'static {
+    const NAME: &'static str;
+}
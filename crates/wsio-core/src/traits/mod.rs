@@ -1 +1,6 @@
+pub mod ack;
+pub mod error;
+pub mod event;
+pub mod packet;
+pub mod stream;
 pub mod task;
@@ -0,0 +1,76 @@
+use std::{
+    any::Any,
+    fmt::Debug,
+};
+
+use anyhow::Result;
+use erased_serde::{
+    deserialize,
+    serialize,
+};
+use serde::{
+    Deserializer,
+    Serialize,
+    Serializer,
+    de::DeserializeOwned,
+};
+
+use crate::packet::WsIoPacket;
+
+// Types
+type ErasedDataDecodeFn = for<'a, 'de> fn(&'a mut dyn erased_serde::Deserializer<'de>) -> Result<Box<dyn Any>>;
+
+// Structs
+/// Decodes event data into the concrete type requested by the caller.
+///
+/// Passed to [`PacketCodec::decode_data`]; the codec only has to provide a serde deserializer for the bytes.
+#[derive(Clone, Copy)]
+pub struct ErasedPacketDataDecoder(ErasedDataDecodeFn);
+
+impl ErasedPacketDataDecoder {
+    #[inline]
+    pub(crate) fn new<D: DeserializeOwned + 'static>() -> Self {
+        Self(|deserializer| Ok(Box::new(deserialize::<D>(deserializer)?)))
+    }
+
+    // Public methods
+    #[inline]
+    pub fn decode<'de, D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<dyn Any>> {
+        (self.0)(&mut <dyn erased_serde::Deserializer>::erase(deserializer))
+    }
+}
+
+/// Event data to be encoded, passed to [`PacketCodec::encode_data`].
+pub struct ErasedPacketData<'a>(&'a dyn erased_serde::Serialize);
+
+impl<'a> ErasedPacketData<'a> {
+    #[inline]
+    pub(crate) fn new<D: Serialize>(data: &'a D) -> Self {
+        Self(data)
+    }
+}
+
+impl Serialize for ErasedPacketData<'_> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+pub trait PacketCodec: Debug + Send + Sync + 'static {
+    /// Decodes a packet envelope, borrowing its fields from `bytes` where possible to avoid copying payloads.
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<WsIoPacket<'a>>;
+
+    fn decode_data(&self, bytes: &[u8], data_decoder: ErasedPacketDataDecoder) -> Result<Box<dyn Any>>;
+
+    /// Encodes a packet envelope. Binary codecs must carry its attachments, text codecs leave them out as they are
+    /// framed around the envelope.
+    fn encode(&self, packet: &WsIoPacket) -> Result<Vec<u8>>;
+
+    fn encode_data(&self, data: &ErasedPacketData) -> Result<Vec<u8>>;
+
+    fn is_text(&self) -> bool;
+
+    /// Unique codec name, advertised by clients during the handshake as the `wsio-{name}` sub-protocol.
+    fn name(&self) -> &str;
+}
@@ -0,0 +1,21 @@
+use anyhow::Result;
+
+use crate::{
+    packet::{
+        WsIoPacket,
+        codecs::WsIoPacketCodec,
+    },
+    stream::registry::WsIoStreamRegistry,
+    traits::{
+        error::reporter::EventErrorReporter,
+        task::spawner::TaskSpawner,
+    },
+};
+
+pub trait StreamPacketSender: EventErrorReporter + Sized + TaskSpawner {
+    fn packet_codec(&self) -> &WsIoPacketCodec;
+
+    fn send_stream_packet(&self, packet: &WsIoPacket<'_>) -> impl Future<Output = Result<()>> + Send;
+
+    fn stream_registry(&self) -> &WsIoStreamRegistry<Self>;
+}
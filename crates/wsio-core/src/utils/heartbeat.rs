@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use tokio::{
+    sync::Notify,
+    time::{
+        sleep,
+        timeout,
+    },
+};
+
+use crate::{
+    event::registry::WsIoEventRegistry,
+    traits::{
+        ack::sender::AckSender,
+        error::{
+            reporter::EventErrorReporter,
+            sender::ErrorSender,
+        },
+        task::spawner::TaskSpawner,
+    },
+};
+
+// Functions
+/// Calls `send_ping` every `heartbeat_interval` and returns once the peer stays silent for longer than
+/// `heartbeat_timeout`, any packet of the peer being signalled through `heartbeat_notify`.
+///
+/// Silence is tolerated while `event_registry` pauses reading, since the packets of the peer, including its pongs,
+/// are not read in the meantime.
+pub async fn run_heartbeat<C, S>(
+    heartbeat_interval: Duration,
+    heartbeat_timeout: Duration,
+    heartbeat_notify: &Notify,
+    event_registry: &WsIoEventRegistry<C, S>,
+    send_ping: impl Fn(),
+) where
+    C: AckSender + ErrorSender + EventErrorReporter,
+    S: TaskSpawner,
+{
+    loop {
+        sleep(heartbeat_interval).await;
+        send_ping();
+
+        loop {
+            let read_pauses = event_registry.read_pauses();
+            match timeout(heartbeat_timeout, heartbeat_notify.notified()).await {
+                Ok(_) => break,
+                Err(_) if event_registry.is_reading_paused() || event_registry.read_pauses() != read_pauses => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::{
+            Arc,
+            atomic::{
+                AtomicUsize,
+                Ordering,
+            },
+        },
+    };
+
+    use tokio::{
+        join,
+        select,
+        spawn,
+        sync::Semaphore,
+    };
+
+    use super::*;
+    use crate::{
+        event::dispatch::{
+            WsIoEventDispatchMode,
+            WsIoEventOverflowPolicy,
+        },
+        testing::TestPeer,
+    };
+
+    // Constants/Statics
+    const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+    const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[tokio::test(start_paused = true)]
+    async fn expires_once_the_peer_stays_silent() {
+        let event_registry = WsIoEventRegistry::<TestPeer, TestPeer>::default();
+        let heartbeat_notify = Notify::new();
+        let ping_count = AtomicUsize::new(0);
+        let pings = Semaphore::new(0);
+        let heartbeat = run_heartbeat(
+            HEARTBEAT_INTERVAL,
+            HEARTBEAT_TIMEOUT,
+            &heartbeat_notify,
+            &event_registry,
+            || {
+                ping_count.fetch_add(1, Ordering::Relaxed);
+                pings.add_permits(1);
+            },
+        );
+
+        // Answer the first three pings, then stay silent
+        let answer_pings = async {
+            for _ in 0..3 {
+                pings.acquire().await.unwrap().forget();
+                heartbeat_notify.notify_one();
+            }
+        };
+
+        join!(answer_pings, heartbeat);
+        assert_eq!(ping_count.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_while_reading_is_paused() {
+        let peer = TestPeer::new();
+        let event_registry = Arc::new(WsIoEventRegistry::new(
+            WsIoEventDispatchMode::Concurrent,
+            None,
+            Some(1),
+            WsIoEventOverflowPolicy::Backpressure,
+            Vec::new(),
+        ));
+
+        let release = Arc::new(Semaphore::new(0));
+        let handler_release = release.clone();
+        event_registry
+            .on("event", move |_, _: Arc<()>| {
+                let release = handler_release.clone();
+                async move {
+                    release.acquire().await?.forget();
+                    Ok(())
+                }
+            })
+            .detach();
+
+        // One event runs and one waits, so reading the third one pauses until the first completes
+        for _ in 0..2 {
+            peer.dispatch(&event_registry, "event", None, None).await.unwrap();
+        }
+
+        let dispatch_peer = peer.clone();
+        let dispatch_event_registry = event_registry.clone();
+        let paused_dispatch = spawn(async move {
+            dispatch_peer
+                .dispatch(&dispatch_event_registry, "event", None, None)
+                .await
+        });
+
+        let heartbeat_notify = Notify::new();
+        let heartbeat = run_heartbeat(
+            HEARTBEAT_INTERVAL,
+            HEARTBEAT_TIMEOUT,
+            &heartbeat_notify,
+            &event_registry,
+            || {},
+        );
+
+        // The heartbeat outlives many timeouts while reading is paused, then expires once it resumes
+        let mut heartbeat = pin!(heartbeat);
+        select! {
+            _ = &mut heartbeat => panic!("Heartbeat expired while reading was paused"),
+            _ = sleep(HEARTBEAT_TIMEOUT * 10) => {}
+        }
+
+        assert!(event_registry.is_reading_paused());
+        release.add_permits(3);
+        paused_dispatch.await.unwrap().unwrap();
+        assert!(!event_registry.is_reading_paused());
+
+        heartbeat.await;
+        peer.wait_for_idle().await;
+    }
+}
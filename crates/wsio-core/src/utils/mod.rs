@@ -1 +1,2 @@
+pub mod heartbeat;
 pub mod task;
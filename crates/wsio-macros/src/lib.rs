@@ -0,0 +1,125 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    DeriveInput,
+    LitStr,
+    Path,
+    Result,
+    parse_macro_input,
+};
+
+// Functions
+/// Derives `WsIoEvent` for a payload type.
+///
+/// The event name defaults to the type name in snake case and can be set with `#[wsio_event(name = "...")]`.
+/// Use `#[wsio_event(crate = "...")]` when `wsio_core` is only reachable through a re-export, e.g.
+/// `wsio_server::core`.
+#[proc_macro_derive(WsIoEvent, attributes(wsio_event))]
+pub fn derive_ws_io_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_ws_io_event(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn expand_ws_io_event(input: DeriveInput) -> Result<TokenStream2> {
+    let mut crate_path: Path = syn::parse_quote!(::wsio_core);
+    let mut name = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("wsio_event")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                crate_path = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                let value = meta.value()?.parse::<LitStr>()?;
+                if value.value().is_empty() {
+                    return Err(meta.error("event name must not be empty"));
+                }
+
+                name = Some(value.value());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported wsio_event attribute, expected `crate` or `name`"))
+            }
+        })?;
+    }
+
+    let ident = &input.ident;
+    let name = name.unwrap_or_else(|| to_snake_case(&ident.to_string()));
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #crate_path::traits::event::definition::WsIoEvent for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+        }
+    })
+}
+
+fn to_snake_case(ident: &str) -> String {
+    let mut snake_case = String::with_capacity(ident.len() + 4);
+    let chars = ident.chars().collect::<Vec<_>>();
+    for (i, char) in chars.iter().enumerate() {
+        if char.is_uppercase() {
+            // Split before an uppercase letter that starts a new word, keeping acronyms such as `HTTP` together
+            if i > 0
+                && (chars[i - 1].is_lowercase()
+                    || chars[i - 1].is_ascii_digit()
+                    || chars.get(i + 1).is_some_and(|next| next.is_lowercase()) && chars[i - 1].is_uppercase())
+            {
+                snake_case.push('_');
+            }
+
+            snake_case.extend(char.to_lowercase());
+        } else {
+            snake_case.push(*char);
+        }
+    }
+
+    snake_case
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_type_names_to_snake_case() {
+        assert_eq!(to_snake_case("ChatMessage"), "chat_message");
+        assert_eq!(to_snake_case("Ping"), "ping");
+        assert_eq!(to_snake_case("HTTPRequest"), "http_request");
+        assert_eq!(to_snake_case("UserID"), "user_id");
+        assert_eq!(to_snake_case("Player2Moved"), "player2_moved");
+        assert_eq!(to_snake_case("already_snake"), "already_snake");
+    }
+
+    #[test]
+    fn names_events_after_their_attribute() {
+        let expanded = expand_ws_io_event(syn::parse_quote! {
+            #[wsio_event(name = "chat:message", crate = "wsio_server::core")]
+            struct ChatMessage;
+        })
+        .unwrap()
+        .to_string();
+
+        assert!(expanded.contains("\"chat:message\""));
+        assert!(expanded.contains("wsio_server :: core :: traits"));
+    }
+
+    #[test]
+    fn rejects_invalid_attributes() {
+        assert!(
+            expand_ws_io_event(syn::parse_quote! {
+                #[wsio_event(name = "")]
+                struct ChatMessage;
+            })
+            .is_err()
+        );
+        assert!(
+            expand_ws_io_event(syn::parse_quote! {
+                #[wsio_event(rename = "chat")]
+                struct ChatMessage;
+            })
+            .is_err()
+        );
+    }
+}
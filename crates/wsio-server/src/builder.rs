@@ -5,7 +5,11 @@ use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use crate::{
     WsIoServer,
     config::WsIoServerConfig,
-    core::packet::codecs::WsIoPacketCodec,
+    core::{
+        event::dispatch::WsIoEventOverflowPolicy,
+        outbound::overflow::WsIoOutboundOverflowPolicy,
+        packet::codecs::WsIoPacketCodec,
+    },
     runtime::WsIoServerRuntime,
 };
 
@@ -19,14 +23,22 @@ impl WsIoServerBuilder {
         Self {
             config: WsIoServerConfig {
                 broadcast_concurrency_limit: 512,
+                event_handler_timeout: None,
+                event_overflow_policy: WsIoEventOverflowPolicy::default(),
+                heartbeat_interval: Some(Duration::from_secs(25)),
+                heartbeat_timeout: Duration::from_secs(20),
                 init_request_handler_timeout: Duration::from_secs(3),
                 init_response_handler_timeout: Duration::from_secs(3),
                 init_response_timeout: Duration::from_secs(5),
+                max_consecutive_event_errors: None,
+                max_in_flight_events: None,
                 middleware_execution_timeout: Duration::from_secs(3),
                 on_close_handler_timeout: Duration::from_secs(2),
                 on_connect_handler_timeout: Duration::from_secs(2),
-                packet_codec: WsIoPacketCodec::SerdeJson,
+                outbound_overflow_policy: WsIoOutboundOverflowPolicy::default(),
+                packet_codecs: vec![WsIoPacketCodec::SerdeJson],
                 request_path: "/ws.io".into(),
+                stream_credit_window: 16,
                 websocket_config: WebSocketConfig::default()
                     .max_frame_size(Some(8 * 1024 * 1024))
                     .max_message_size(Some(16 * 1024 * 1024))
@@ -47,6 +59,32 @@ impl WsIoServerBuilder {
         WsIoServer(WsIoServerRuntime::new(self.config))
     }
 
+    /// Stops pinging connections, relying on the transport to notice dead ones.
+    pub fn disable_heartbeat(mut self) -> Self {
+        self.config.heartbeat_interval = None;
+        self
+    }
+
+    pub fn event_handler_timeout(mut self, duration: Duration) -> Self {
+        self.config.event_handler_timeout = Some(duration);
+        self
+    }
+
+    pub fn event_overflow_policy(mut self, event_overflow_policy: WsIoEventOverflowPolicy) -> Self {
+        self.config.event_overflow_policy = event_overflow_policy;
+        self
+    }
+
+    pub fn heartbeat_interval(mut self, duration: Duration) -> Self {
+        self.config.heartbeat_interval = Some(duration);
+        self
+    }
+
+    pub fn heartbeat_timeout(mut self, duration: Duration) -> Self {
+        self.config.heartbeat_timeout = duration;
+        self
+    }
+
     pub fn init_request_handler_timeout(mut self, duration: Duration) -> Self {
         self.config.init_request_handler_timeout = duration;
         self
@@ -62,6 +100,16 @@ impl WsIoServerBuilder {
         self
     }
 
+    pub fn max_consecutive_event_errors(mut self, max_consecutive_event_errors: u32) -> Self {
+        self.config.max_consecutive_event_errors = Some(max_consecutive_event_errors);
+        self
+    }
+
+    pub fn max_in_flight_events(mut self, max_in_flight_events: usize) -> Self {
+        self.config.max_in_flight_events = Some(max_in_flight_events);
+        self
+    }
+
     pub fn middleware_execution_timeout(mut self, duration: Duration) -> Self {
         self.config.middleware_execution_timeout = duration;
         self
@@ -77,8 +125,18 @@ impl WsIoServerBuilder {
         self
     }
 
-    pub fn packet_codec(mut self, packet_codec: WsIoPacketCodec) -> Self {
-        self.config.packet_codec = packet_codec;
+    pub fn outbound_overflow_policy(mut self, policy: WsIoOutboundOverflowPolicy) -> Self {
+        self.config.outbound_overflow_policy = policy;
+        self
+    }
+
+    pub fn packet_codec(mut self, packet_codec: impl Into<WsIoPacketCodec>) -> Self {
+        self.config.packet_codecs = vec![packet_codec.into()];
+        self
+    }
+
+    pub fn packet_codecs<I: IntoIterator<Item = C>, C: Into<WsIoPacketCodec>>(mut self, packet_codecs: I) -> Self {
+        self.config.packet_codecs = packet_codecs.into_iter().map(Into::into).collect();
         self
     }
 
@@ -87,6 +145,11 @@ impl WsIoServerBuilder {
         self
     }
 
+    pub fn stream_credit_window(mut self, stream_credit_window: u32) -> Self {
+        self.config.stream_credit_window = stream_credit_window;
+        self
+    }
+
     pub fn websocket_config(mut self, websocket_config: WebSocketConfig) -> Self {
         self.config.websocket_config = websocket_config;
         self
@@ -2,7 +2,11 @@ use std::time::Duration;
 
 use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 
-use crate::core::packet::codecs::WsIoPacketCodec;
+use crate::core::{
+    event::dispatch::WsIoEventOverflowPolicy,
+    outbound::overflow::WsIoOutboundOverflowPolicy,
+    packet::codecs::WsIoPacketCodec,
+};
 
 // Structs
 #[derive(Debug)]
@@ -12,6 +16,26 @@ pub(crate) struct WsIoServerConfig {
     /// Can be overridden by namespace-level configuration.
     pub(crate) broadcast_concurrency_limit: usize,
 
+    /// Action taken when a connection sends an event while `max_in_flight_events` of its events are being handled.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) event_overflow_policy: WsIoEventOverflowPolicy,
+
+    /// Maximum duration allowed for an event handler to execute, unless overridden when it is registered.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) event_handler_timeout: Option<Duration>,
+
+    /// Interval between ping packets sent to each connection, `None` disabling the heartbeat.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) heartbeat_interval: Option<Duration>,
+
+    /// Maximum duration to wait for any packet from the client after a ping before closing the connection.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) heartbeat_timeout: Duration,
+
     /// Maximum duration allowed for the init request handler to execute.
     ///
     /// Can be overridden by namespace-level configuration.
@@ -27,6 +51,16 @@ pub(crate) struct WsIoServerConfig {
     /// Can be overridden by namespace-level configuration.
     pub(crate) init_response_timeout: Duration,
 
+    /// Number of consecutive event handler or payload decode failures after which a connection is disconnected.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) max_consecutive_event_errors: Option<u32>,
+
+    /// Maximum number of events per connection whose handlers may be running at the same time.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) max_in_flight_events: Option<usize>,
+
     /// Maximum duration allowed for middleware execution.
     ///
     /// Can be overridden by namespace-level configuration.
@@ -42,11 +76,25 @@ pub(crate) struct WsIoServerConfig {
     /// Can be overridden by namespace-level configuration.
     pub(crate) on_connect_handler_timeout: Duration,
 
+    /// Action taken when a message is sent to a connection whose outbound queue is full.
+    ///
     /// Can be overridden by namespace-level configuration.
-    pub(crate) packet_codec: WsIoPacketCodec,
+    pub(crate) outbound_overflow_policy: WsIoOutboundOverflowPolicy,
+
+    /// Packet codecs accepted during the handshake, in order of preference.
+    ///
+    /// The first one is used for clients that do not advertise a codec.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) packet_codecs: Vec<WsIoPacketCodec>,
 
     pub(crate) request_path: String,
 
+    /// Number of chunks a connection may send on each stream it opens before waiting for more credit.
+    ///
+    /// Can be overridden by namespace-level configuration.
+    pub(crate) stream_credit_window: u32,
+
     /// Can be overridden by namespace-level configuration.
     pub(crate) websocket_config: WebSocketConfig,
 }
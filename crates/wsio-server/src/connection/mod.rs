@@ -1,10 +1,16 @@
-use std::sync::{
-    Arc,
-    LazyLock,
-    atomic::{
-        AtomicU64,
-        Ordering,
+use std::{
+    future::pending,
+    sync::{
+        Arc,
+        LazyLock,
+        atomic::{
+            AtomicU16,
+            AtomicU32,
+            AtomicU64,
+            Ordering,
+        },
     },
+    time::Duration,
 };
 
 use anyhow::{
@@ -28,11 +34,7 @@ use tokio::{
     spawn,
     sync::{
         Mutex,
-        mpsc::{
-            Receiver,
-            Sender,
-            channel,
-        },
+        Notify,
     },
     task::JoinHandle,
     time::{
@@ -40,7 +42,14 @@ use tokio::{
         timeout,
     },
 };
-use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::{
+    Bytes,
+    Message,
+    protocol::{
+        CloseFrame,
+        frame::coding::CloseCode,
+    },
+};
 use tokio_util::sync::CancellationToken;
 
 #[cfg(feature = "connection-extensions")]
@@ -51,19 +60,64 @@ use self::extensions::ConnectionExtensions;
 use crate::{
     WsIoServer,
     core::{
+        ack::registry::WsIoAckRegistry,
         atomic::status::AtomicStatus,
         channel_capacity_from_websocket_config,
-        event::registry::WsIoEventRegistry,
+        error::{
+            UNSUPPORTED_PROTOCOL_VERSION_ERROR_CODE,
+            WsIoError,
+            WsIoRemoteError,
+            encode_error_packet,
+        },
+        event::{
+            pattern::WsIoEventParams,
+            registry::{
+                WsIoEventPayload,
+                WsIoEventRegistry,
+            },
+            subscription::WsIoSubscription,
+        },
+        outbound::{
+            overflow::WsIoOutboundOverflowPolicy,
+            priority::WsIoOutboundPriority,
+            queue::{
+                WsIoOutboundMessage,
+                WsIoOutboundQueue,
+                WsIoOutboundQueueReceiver,
+            },
+        },
         packet::{
             WsIoPacket,
             WsIoPacketType,
+            codecs::WsIoPacketCodec,
+        },
+        protocol::version::{
+            WS_IO_PROTOCOL_VERSION,
+            negotiate_protocol_version,
+        },
+        stream::{
+            receiver::WsIoStreamReceiver,
+            registry::WsIoStreamRegistry,
+            sender::WsIoStreamSender,
+        },
+        traits::{
+            ack::sender::AckSender,
+            error::{
+                reporter::EventErrorReporter,
+                sender::ErrorSender,
+            },
+            event::definition::WsIoEvent,
+            stream::sender::StreamPacketSender,
+            task::spawner::TaskSpawner,
         },
-        traits::task::spawner::TaskSpawner,
         types::{
             BoxAsyncUnaryResultHandler,
             hashers::FxDashSet,
         },
-        utils::task::abort_locked_task,
+        utils::{
+            heartbeat::run_heartbeat,
+            task::abort_locked_task,
+        },
     },
     namespace::{
         WsIoServerNamespace,
@@ -86,19 +140,88 @@ enum ConnectionStatus {
 
 // Structs
 pub struct WsIoServerConnection {
+    ack_registry: WsIoAckRegistry,
     cancel_token: ArcSwap<CancellationToken>,
+    consecutive_event_errors: AtomicU32,
+    dropped_message_count: AtomicU64,
     event_registry: WsIoEventRegistry<WsIoServerConnection, WsIoServerConnection>,
     #[cfg(feature = "connection-extensions")]
     extensions: ConnectionExtensions,
     headers: HeaderMap,
+    heartbeat_notify: Notify,
     id: u64,
     init_timeout_task: Mutex<Option<JoinHandle<()>>>,
     joined_rooms: FxDashSet<String>,
-    message_tx: Sender<Arc<Message>>,
+    message_queue: WsIoOutboundQueue,
     namespace: Arc<WsIoServerNamespace>,
     on_close_handler: Mutex<Option<BoxAsyncUnaryResultHandler<Self>>>,
+    packet_codec_index: usize,
+    protocol_version: AtomicU16,
     request_uri: Uri,
     status: AtomicStatus<ConnectionStatus>,
+    stream_registry: WsIoStreamRegistry<WsIoServerConnection>,
+}
+
+impl AckSender for WsIoServerConnection {
+    #[inline]
+    async fn send_ack(&self, ack_id: u32, data: Option<Vec<u8>>) -> Result<()> {
+        self.send_packet(&WsIoPacket::new_ack(ack_id, data)).await
+    }
+}
+
+impl ErrorSender for WsIoServerConnection {
+    #[inline]
+    async fn send_error(&self, error: &anyhow::Error, ack_id: Option<u32>) -> Result<()> {
+        self.send_packet(&WsIoPacket {
+            ack_id,
+            ..encode_error_packet(error, self.packet_codec())?
+        })
+        .await
+    }
+}
+
+impl EventErrorReporter for WsIoServerConnection {
+    #[inline]
+    fn clear_event_errors(&self) {
+        self.consecutive_event_errors.store(0, Ordering::Relaxed);
+    }
+
+    async fn report_event_error(self: &Arc<Self>, event: &str, error: anyhow::Error) {
+        let consecutive_event_errors = self.consecutive_event_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(on_event_error_handler) = &self.namespace.config.on_event_error_handler {
+            let _ = on_event_error_handler(self.clone(), event.into(), error).await;
+        }
+
+        if self
+            .namespace
+            .config
+            .max_consecutive_event_errors
+            .is_some_and(|max_consecutive_event_errors| consecutive_event_errors >= max_consecutive_event_errors)
+        {
+            self.disconnect().await;
+        }
+    }
+}
+
+impl StreamPacketSender for WsIoServerConnection {
+    #[inline]
+    fn packet_codec(&self) -> &WsIoPacketCodec {
+        self.packet_codec()
+    }
+
+    #[inline]
+    async fn send_stream_packet(&self, packet: &WsIoPacket<'_>) -> Result<()> {
+        self.emit_event_message(
+            WsIoOutboundMessage::from(self.namespace.encode_packet_to_message(packet, self.packet_codec())?)
+                .with_priority(WsIoOutboundPriority::for_packet_type(packet.r#type)),
+        )
+        .await
+    }
+
+    #[inline]
+    fn stream_registry(&self) -> &WsIoStreamRegistry<Self> {
+        &self.stream_registry
+    }
 }
 
 impl TaskSpawner for WsIoServerConnection {
@@ -113,60 +236,52 @@ impl WsIoServerConnection {
     pub(crate) fn new(
         headers: HeaderMap,
         namespace: Arc<WsIoServerNamespace>,
+        packet_codec_index: usize,
         request_uri: Uri,
-    ) -> (Arc<Self>, Receiver<Arc<Message>>) {
+    ) -> (Arc<Self>, WsIoOutboundQueueReceiver) {
         let channel_capacity = channel_capacity_from_websocket_config(&namespace.config.websocket_config);
-        let (message_tx, message_rx) = channel(channel_capacity);
+        let (message_queue, message_rx) = WsIoOutboundQueue::new(channel_capacity);
+        let stream_registry = WsIoStreamRegistry::new(namespace.config.stream_credit_window);
         (
             Arc::new(Self {
+                ack_registry: WsIoAckRegistry::new(),
                 cancel_token: ArcSwap::new(Arc::new(CancellationToken::new())),
-                event_registry: WsIoEventRegistry::new(),
+                consecutive_event_errors: AtomicU32::new(0),
+                dropped_message_count: AtomicU64::new(0),
+                event_registry: WsIoEventRegistry::new(
+                    namespace.config.event_dispatch_mode,
+                    namespace.config.event_handler_timeout,
+                    namespace.config.max_in_flight_events,
+                    namespace.config.event_overflow_policy,
+                    namespace.config.event_interceptors.clone(),
+                ),
                 #[cfg(feature = "connection-extensions")]
                 extensions: ConnectionExtensions::new(),
                 headers,
+                heartbeat_notify: Notify::new(),
                 id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
                 init_timeout_task: Mutex::new(None),
                 joined_rooms: FxDashSet::default(),
-                message_tx,
+                message_queue,
                 namespace,
                 on_close_handler: Mutex::new(None),
+                packet_codec_index,
+                protocol_version: AtomicU16::new(0),
                 request_uri,
                 status: AtomicStatus::new(ConnectionStatus::Created),
+                stream_registry,
             }),
             message_rx,
         )
     }
 
     // Private methods
-    #[inline]
-    fn handle_event_packet(self: &Arc<Self>, event: &str, packet_data: Option<Vec<u8>>) -> Result<()> {
-        self.event_registry.dispatch_event_packet(
-            self.clone(),
-            event,
-            &self.namespace.config.packet_codec,
-            packet_data,
-            self,
-        );
-
-        Ok(())
-    }
-
-    async fn handle_init_packet(self: &Arc<Self>, packet_data: Option<&[u8]>) -> Result<()> {
-        // Verify current state; only valid from AwaitingInit → Initiating
-        let status = self.status.get();
-        match status {
-            ConnectionStatus::AwaitingInit => self.status.try_transition(status, ConnectionStatus::Initiating)?,
-            _ => bail!("Received init packet in invalid status: {status:?}"),
-        }
-
-        // Abort init-timeout task if still active
-        abort_locked_task(&self.init_timeout_task).await;
-
+    async fn activate(self: &Arc<Self>, packet_data: Option<&[u8]>) -> Result<()> {
         // Invoke init_response handler with timeout protection if configured
         if let Some(init_response_handler) = &self.namespace.config.init_response_handler {
             timeout(
                 self.namespace.config.init_response_handler_timeout,
-                init_response_handler(self.clone(), packet_data, &self.namespace.config.packet_codec),
+                init_response_handler(self.clone(), packet_data, self.packet_codec()),
             )
             .await??
         }
@@ -217,11 +332,145 @@ impl WsIoServerConnection {
         Ok(())
     }
 
-    async fn send_packet(&self, packet: &WsIoPacket) -> Result<()> {
-        self.send_message(self.namespace.encode_packet_to_message(packet)?)
+    #[inline]
+    fn close_with_frame(&self, close_frame: Option<CloseFrame>) {
+        // Skip if connection is already Closing or Closed, otherwise set connection state to Closing
+        match self.status.get() {
+            ConnectionStatus::Closed | ConnectionStatus::Closing => return,
+            _ => self.status.store(ConnectionStatus::Closing),
+        }
+
+        // Send websocket close frame to initiate graceful shutdown, discarding the backlog if it leaves no room for it
+        let message = Arc::new(Message::Close(close_frame));
+        if let Ok(false) = self.message_queue.try_push(message.clone())
+            && let Ok(discarded) = self.message_queue.clear_and_push(message)
+        {
+            self.dropped_message_count
+                .fetch_add(discarded as u64, Ordering::Relaxed);
+        }
+    }
+
+    async fn emit_event_message_with_overflow_policy(
+        &self,
+        message: impl Into<WsIoOutboundMessage>,
+        overflow_policy: WsIoOutboundOverflowPolicy,
+    ) -> Result<()> {
+        self.status.ensure(ConnectionStatus::Ready, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        self.send_message_with_overflow_policy(message, overflow_policy).await
+    }
+
+    #[inline]
+    async fn handle_event_packet(
+        self: &Arc<Self>,
+        event: &str,
+        packet_data: Option<Bytes>,
+        attachments: Vec<Bytes>,
+        ack_id: Option<u32>,
+    ) -> Result<()> {
+        self.event_registry
+            .dispatch_event_packet(
+                self.clone(),
+                event,
+                self.packet_codec(),
+                WsIoEventPayload {
+                    attachments,
+                    data: packet_data,
+                },
+                ack_id,
+                self,
+            )
             .await
     }
 
+    async fn handle_init_packet(self: &Arc<Self>, packet_data: Option<&[u8]>, version: Option<u16>) -> Result<()> {
+        // Verify current state; only valid from AwaitingInit → Initiating
+        let status = self.status.get();
+        match status {
+            ConnectionStatus::AwaitingInit => self.status.try_transition(status, ConnectionStatus::Initiating)?,
+            _ => bail!("Received init packet in invalid status: {status:?}"),
+        }
+
+        // Abort init-timeout task if still active
+        abort_locked_task(&self.init_timeout_task).await;
+
+        // Negotiate protocol version, closing with the reason if the client is incompatible
+        match negotiate_protocol_version(version) {
+            Ok(protocol_version) => self.protocol_version.store(protocol_version, Ordering::Relaxed),
+            Err(err) => {
+                let error = WsIoError::new(UNSUPPORTED_PROTOCOL_VERSION_ERROR_CODE, err.to_string());
+                let _ = self.send_error(&error.into(), None).await;
+                self.close_with_frame(Some(CloseFrame {
+                    code: CloseCode::Protocol,
+                    reason: err.to_string().into(),
+                }));
+
+                return Ok(());
+            }
+        }
+
+        // Run handshake handlers, rejecting the connection with an error packet if any of them fails
+        if let Err(err) = self.activate(packet_data).await {
+            let _ = self.send_error(&err, None).await;
+            self.close();
+        }
+
+        Ok(())
+    }
+
+    async fn send_packet(&self, packet: &WsIoPacket<'_>) -> Result<()> {
+        self.send_message(
+            WsIoOutboundMessage::from(self.namespace.encode_packet_to_message(packet, self.packet_codec())?)
+                .with_priority(WsIoOutboundPriority::for_packet_type(packet.r#type)),
+        )
+        .await
+    }
+
+    /// Queues `message`, applying `overflow_policy` if the queue is full.
+    async fn send_message_with_overflow_policy(
+        &self,
+        message: impl Into<WsIoOutboundMessage>,
+        overflow_policy: WsIoOutboundOverflowPolicy,
+    ) -> Result<()> {
+        let message = message.into();
+        match overflow_policy {
+            WsIoOutboundOverflowPolicy::Block => {
+                self.message_queue.push(message, None).await?;
+            }
+            WsIoOutboundOverflowPolicy::BlockWithTimeout(duration) => {
+                if !self.message_queue.push(message, Some(duration)).await? {
+                    self.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+                    bail!("Outbound queue stayed full for {duration:?}");
+                }
+            }
+            WsIoOutboundOverflowPolicy::Disconnect => {
+                if !self.message_queue.try_push(message)? {
+                    self.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+                    self.close_with_frame(Some(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "Outbound queue overflowed".into(),
+                    }));
+
+                    bail!("Outbound queue full, closing connection");
+                }
+            }
+            WsIoOutboundOverflowPolicy::DropNewest => {
+                if !self.message_queue.try_push(message)? {
+                    self.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            WsIoOutboundOverflowPolicy::DropOldest => {
+                if self.message_queue.push_evicting_oldest(message)? {
+                    self.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     // Protected methods
     pub(crate) async fn cleanup(self: &Arc<Self>) {
         // Set connection state to Closing
@@ -244,6 +493,12 @@ impl WsIoServerConnection {
         // Cancel all ongoing operations via cancel token
         self.cancel_token.load().cancel();
 
+        // Drop all pending acks so their waiters fail immediately
+        self.ack_registry.clear();
+
+        // Fail all open streams so their senders and receivers stop waiting
+        self.stream_registry.clear();
+
         // Invoke on_close handler with timeout protection if configured
         if let Some(on_close_handler) = self.on_close_handler.lock().await.take() {
             let _ = timeout(
@@ -259,36 +514,63 @@ impl WsIoServerConnection {
 
     #[inline]
     pub(crate) fn close(&self) {
-        // Skip if connection is already Closing or Closed, otherwise set connection state to Closing
-        match self.status.get() {
-            ConnectionStatus::Closed | ConnectionStatus::Closing => return,
-            _ => self.status.store(ConnectionStatus::Closing),
-        }
-
-        // Send websocket close frame to initiate graceful shutdown
-        let _ = self.message_tx.try_send(Arc::new(Message::Close(None)));
+        self.close_with_frame(None);
     }
 
-    pub(crate) async fn emit_event_message(&self, message: Arc<Message>) -> Result<()> {
-        self.status.ensure(ConnectionStatus::Ready, |status| {
-            format!("Cannot emit in invalid status: {status:?}")
-        })?;
+    #[inline]
+    pub(crate) async fn emit_event_message(&self, message: impl Into<WsIoOutboundMessage>) -> Result<()> {
+        self.emit_event_message_with_overflow_policy(message, self.namespace.config.outbound_overflow_policy)
+            .await
+    }
 
-        self.send_message(message).await
+    /// Queues `message` without waiting, dropping it if the connection is not ready or its queue is full.
+    #[inline]
+    pub(crate) fn emit_volatile_event_message(&self, message: Arc<Message>) {
+        if self.status.is(ConnectionStatus::Ready) && matches!(self.message_queue.try_push(message), Ok(false)) {
+            self.dropped_message_count.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
-    pub(crate) async fn handle_incoming_packet(self: &Arc<Self>, bytes: &[u8]) -> Result<()> {
-        // TODO: lazy load
-        let packet = self.namespace.config.packet_codec.decode(bytes)?;
+    pub(crate) async fn handle_incoming_packet(self: &Arc<Self>, frame: Bytes, is_binary: bool) -> Result<()> {
+        // Any packet proves the client is alive
+        self.heartbeat_notify.notify_one();
+
+        // Payload stays borrowed from the frame until a registered handler needs it
+        let mut packet = self.packet_codec().decode_frame(&frame, is_binary)?;
         match packet.r#type {
+            WsIoPacketType::Ack => {
+                if let Some(ack_id) = packet.ack_id {
+                    self.ack_registry.resolve(ack_id, packet.take_frame_data(&frame));
+                    Ok(())
+                } else {
+                    bail!("Ack packet missing ack id");
+                }
+            }
+            WsIoPacketType::Error => {
+                if let Some(ack_id) = packet.ack_id {
+                    self.ack_registry
+                        .reject(ack_id, WsIoRemoteError::from_packet(packet, self.packet_codec()));
+                }
+
+                Ok(())
+            }
             WsIoPacketType::Event => {
+                let packet_data = packet.take_frame_data(&frame);
+                let attachments = packet.take_frame_attachments(&frame);
                 if let Some(event) = packet.key.as_deref() {
-                    self.handle_event_packet(event, packet.data)
+                    self.handle_event_packet(event, packet_data, attachments, packet.ack_id)
+                        .await
                 } else {
                     bail!("Event packet missing key");
                 }
             }
-            WsIoPacketType::Init => self.handle_init_packet(packet.data.as_deref()).await,
+            WsIoPacketType::Init => self.handle_init_packet(packet.data.as_deref(), packet.version).await,
+            WsIoPacketType::Ping => self.send_packet(&WsIoPacket::new_pong()).await,
+            WsIoPacketType::StreamCancel
+            | WsIoPacketType::StreamCredit
+            | WsIoPacketType::StreamData
+            | WsIoPacketType::StreamEnd
+            | WsIoPacketType::StreamOpen => self.stream_registry.handle_packet(self, packet, &frame).await,
             _ => Ok(()),
         }
     }
@@ -303,7 +585,7 @@ impl WsIoServerConnection {
         let init_request_data = if let Some(init_request_handler) = &self.namespace.config.init_request_handler {
             timeout(
                 self.namespace.config.init_request_handler_timeout,
-                init_request_handler(self.clone(), &self.namespace.config.packet_codec),
+                init_request_handler(self.clone(), self.packet_codec()),
             )
             .await??
         } else {
@@ -324,11 +606,47 @@ impl WsIoServerConnection {
         }));
 
         // Send init packet
-        self.send_packet(&WsIoPacket::new_init(init_request_data)).await
+        self.send_packet(&WsIoPacket::new_init(WS_IO_PROTOCOL_VERSION, init_request_data))
+            .await
+    }
+
+    #[inline]
+    pub(crate) fn packet_codec_index(&self) -> usize {
+        self.packet_codec_index
     }
 
-    pub(crate) async fn send_message(&self, message: Arc<Message>) -> Result<()> {
-        Ok(self.message_tx.send(message).await?)
+    /// Sends pings periodically and returns once the client stays silent for longer than the heartbeat timeout, never
+    /// returning if the heartbeat is disabled. Silence is tolerated while reading is paused by the in-flight limit.
+    pub(crate) async fn run_heartbeat(&self) {
+        let Some(heartbeat_interval) = self.namespace.config.heartbeat_interval else {
+            return pending().await;
+        };
+
+        run_heartbeat(
+            heartbeat_interval,
+            self.namespace.config.heartbeat_timeout,
+            &self.heartbeat_notify,
+            &self.event_registry,
+            || {
+                // Skip the ping if the outgoing queue is full, the pending traffic will keep the client busy anyway
+                if let Ok(message) = self
+                    .namespace
+                    .encode_packet_to_message(&WsIoPacket::new_ping(), self.packet_codec())
+                {
+                    let _ = self
+                        .message_queue
+                        .try_push(WsIoOutboundMessage::from(message).with_priority(WsIoOutboundPriority::High));
+                }
+            },
+        )
+        .await
+    }
+
+    /// Queues `message`, applying the outbound overflow policy of the namespace if the queue is full.
+    #[inline]
+    pub(crate) async fn send_message(&self, message: impl Into<WsIoOutboundMessage>) -> Result<()> {
+        self.send_message_with_overflow_policy(message, self.namespace.config.outbound_overflow_policy)
+            .await
     }
 
     // Public methods
@@ -337,13 +655,159 @@ impl WsIoServerConnection {
         self.close()
     }
 
+    /// Number of messages dropped because the outbound queue was full, by volatile emits or the overflow policy.
+    #[inline]
+    pub fn dropped_message_count(&self) -> u64 {
+        self.dropped_message_count.load(Ordering::Relaxed)
+    }
+
     pub async fn emit<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
+        self.emit_event_message(self.namespace.encode_packet_to_message(
+            &WsIoPacket::new_event(
+                event.as_ref(),
+                data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+            ),
+            self.packet_codec(),
+        )?)
+        .await
+    }
+
+    /// Emits `event`, replacing the message queued for this connection with the same `coalescing_key` if it has not
+    /// been sent yet.
+    ///
+    /// Meant for state where only the latest value matters, such as the position of a player.
+    pub async fn emit_coalesced<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        coalescing_key: impl AsRef<str>,
+    ) -> Result<()> {
+        self.emit_event_message(WsIoOutboundMessage::coalesced(
+            self.namespace.encode_packet_to_message(
+                &WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+                ),
+                self.packet_codec(),
+            )?,
+            coalescing_key.as_ref().into(),
+        ))
+        .await
+    }
+
+    pub async fn emit_event<E: WsIoEvent>(&self, event: &E) -> Result<()> {
+        self.emit(E::NAME, Some(event)).await
+    }
+
+    pub async fn emit_event_with_ack<E: WsIoEvent, R: DeserializeOwned + 'static>(
+        &self,
+        event: &E,
+        timeout: Duration,
+    ) -> Result<R> {
+        self.emit_with_ack(E::NAME, Some(event), timeout).await
+    }
+
+    /// Emits `event` with a payload that is already encoded with the packet codec of this connection.
+    pub async fn emit_raw(&self, event: impl AsRef<str>, data: Option<&[u8]>) -> Result<()> {
         self.emit_event_message(
-            self.namespace.encode_packet_to_message(&WsIoPacket::new_event(
+            self.namespace
+                .encode_packet_to_message(&WsIoPacket::new_raw_event(event.as_ref(), data), self.packet_codec())?,
+        )
+        .await
+    }
+
+    /// Emits `event`, waiting at most `timeout` for room in the outbound queue regardless of the overflow policy.
+    ///
+    /// A message that times out is counted in [`Self::dropped_message_count`].
+    pub async fn emit_timeout<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        timeout: Duration,
+    ) -> Result<()> {
+        self.emit_event_message_with_overflow_policy(
+            self.namespace.encode_packet_to_message(
+                &WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+                ),
+                self.packet_codec(),
+            )?,
+            WsIoOutboundOverflowPolicy::BlockWithTimeout(timeout),
+        )
+        .await
+    }
+
+    /// Emits `event` without waiting for room in the outgoing queue, dropping it if the connection is not ready or
+    /// the queue is full.
+    ///
+    /// Meant for data that is soon superseded, such as cursor positions or live prices.
+    pub fn emit_volatile<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
+        if !self.status.is(ConnectionStatus::Ready) {
+            return Ok(());
+        }
+
+        self.emit_volatile_event_message(self.namespace.encode_packet_to_message(
+            &WsIoPacket::new_event(
                 event.as_ref(),
-                data.map(|data| self.namespace.config.packet_codec.encode_data(data))
-                    .transpose()?,
-            ))?,
+                data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+            ),
+            self.packet_codec(),
+        )?);
+
+        Ok(())
+    }
+
+    pub async fn emit_with_ack<D: Serialize, R: DeserializeOwned + 'static>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        timeout: Duration,
+    ) -> Result<R> {
+        let packet_data = data.map(|data| self.packet_codec().encode_data(data)).transpose()?;
+
+        let pending_ack = self.ack_registry.register();
+        let packet = WsIoPacket::new_event_with_ack(event.as_ref(), pending_ack.ack_id(), packet_data);
+        self.emit_event_message(self.namespace.encode_packet_to_message(&packet, self.packet_codec())?)
+            .await?;
+
+        pending_ack.wait(timeout, self.packet_codec()).await
+    }
+
+    /// Emits `event` with `attachments` sent as binary blobs next to its payload, without going through the codec.
+    pub async fn emit_with_attachments<D: Serialize, B: AsRef<[u8]>>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        attachments: &[B],
+    ) -> Result<()> {
+        self.emit_event_message(self.namespace.encode_packet_to_message(
+            &WsIoPacket::new_event_with_attachments(
+                event.as_ref(),
+                data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+                attachments.iter().map(AsRef::as_ref).collect(),
+            ),
+            self.packet_codec(),
+        )?)
+        .await
+    }
+
+    /// Emits `event` through the outbound lane of `priority`, high priority messages overtaking the queued ones.
+    pub async fn emit_with_priority<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        priority: WsIoOutboundPriority,
+    ) -> Result<()> {
+        self.emit_event_message(
+            WsIoOutboundMessage::from(self.namespace.encode_packet_to_message(
+                &WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+                ),
+                self.packet_codec(),
+            )?)
+            .with_priority(priority),
         )
         .await
     }
@@ -402,20 +866,50 @@ impl WsIoServerConnection {
     }
 
     #[inline]
-    pub fn off_by_handler_id(&self, event: impl AsRef<str>, handler_id: u32) {
+    pub fn off_any(&self) {
+        self.event_registry.off_any();
+    }
+
+    #[inline]
+    pub fn off_any_by_handler_id(&self, handler_id: u64) {
+        self.event_registry.off_any_by_handler_id(handler_id);
+    }
+
+    #[inline]
+    pub fn off_by_handler_id(&self, event: impl AsRef<str>, handler_id: u64) {
         self.event_registry.off_by_handler_id(event.as_ref(), handler_id);
     }
 
     #[inline]
-    pub fn on<H, Fut, D>(&self, event: impl AsRef<str>, handler: H) -> u32
+    pub fn off_event<E: WsIoEvent>(&self) {
+        self.event_registry.off(E::NAME);
+    }
+
+    #[inline]
+    pub fn off_stream(&self, stream: impl AsRef<str>) {
+        self.stream_registry.off(stream.as_ref());
+    }
+
+    #[inline]
+    pub fn on<H, Fut, D, R>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
     where
         H: Fn(Arc<WsIoServerConnection>, Arc<D>) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<()>> + Send + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
         D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
     {
         self.event_registry.on(event.as_ref(), handler)
     }
 
+    #[inline]
+    pub fn on_any<H, Fut>(&self, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoServerConnection>, String, Option<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.event_registry.on_any(handler)
+    }
+
     pub async fn on_close<H, Fut>(&self, handler: H)
     where
         H: Fn(Arc<WsIoServerConnection>) -> Fut + Send + Sync + 'static,
@@ -424,6 +918,122 @@ impl WsIoServerConnection {
         *self.on_close_handler.lock().await = Some(Box::new(move |connection| Box::pin(handler(connection))));
     }
 
+    #[inline]
+    pub fn on_event<E, H, Fut, R>(&self, handler: H) -> WsIoSubscription
+    where
+        E: WsIoEvent,
+        H: Fn(Arc<WsIoServerConnection>, Arc<E>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on(E::NAME, handler)
+    }
+
+    #[inline]
+    pub fn on_pattern<H, Fut, D, R>(&self, pattern: impl AsRef<str>, handler: H) -> Result<WsIoSubscription>
+    where
+        H: Fn(Arc<WsIoServerConnection>, Arc<D>, WsIoEventParams) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on_pattern(pattern.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn on_raw<H, Fut>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoServerConnection>, String, Option<Bytes>, WsIoPacketCodec) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<Vec<u8>>>> + Send + 'static,
+    {
+        self.event_registry.on_raw(event.as_ref(), handler)
+    }
+
+    /// Registers the handler of the streams named `stream` opened by the client, replacing any previous one.
+    #[inline]
+    pub fn on_stream<H, Fut, D>(&self, stream: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoServerConnection>, Arc<D>, WsIoStreamReceiver<WsIoServerConnection>) -> Fut
+            + Send
+            + Sync
+            + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.stream_registry.on(stream.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn on_with_attachments<H, Fut, D, R>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoServerConnection>, Arc<D>, Vec<Bytes>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on_with_attachments(event.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn on_with_timeout<H, Fut, D, R>(
+        &self,
+        event: impl AsRef<str>,
+        timeout: Duration,
+        handler: H,
+    ) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoServerConnection>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.on_with_timeout(event.as_ref(), timeout, handler)
+    }
+
+    #[inline]
+    pub fn once<H, Fut, D, R>(&self, event: impl AsRef<str>, handler: H) -> WsIoSubscription
+    where
+        H: Fn(Arc<WsIoServerConnection>, Arc<D>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        D: DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.once(event.as_ref(), handler)
+    }
+
+    #[inline]
+    pub fn once_event<E, H, Fut, R>(&self, handler: H) -> WsIoSubscription
+    where
+        E: WsIoEvent,
+        H: Fn(Arc<WsIoServerConnection>, Arc<E>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        R: Serialize + Send + 'static,
+    {
+        self.event_registry.once(E::NAME, handler)
+    }
+
+    /// Opens a stream named `stream` with the client, sending `data` along with the request.
+    pub async fn open_stream<D: Serialize>(
+        self: &Arc<Self>,
+        stream: impl AsRef<str>,
+        data: Option<&D>,
+    ) -> Result<WsIoStreamSender<WsIoServerConnection>> {
+        let packet_data = data.map(|data| self.packet_codec().encode_data(data)).transpose()?;
+        self.stream_registry.open(self, stream.as_ref(), packet_data).await
+    }
+
+    /// Packet codec negotiated with the client during the handshake.
+    #[inline]
+    pub fn packet_codec(&self) -> &WsIoPacketCodec {
+        &self.namespace.config.packet_codecs[self.packet_codec_index]
+    }
+
+    /// Protocol version negotiated with the client, `0` until its init packet is received.
+    #[inline]
+    pub fn protocol_version(&self) -> u16 {
+        self.protocol_version.load(Ordering::Relaxed)
+    }
+
     #[inline]
     pub fn request_uri(&self) -> &Uri {
         &self.request_uri
@@ -441,6 +1051,42 @@ impl WsIoServerConnection {
     ) -> WsIoServerNamespaceBroadcastOperator {
         self.namespace.to(room_names).except_connection_ids(vec![self.id])
     }
+
+    /// Emits `event` only if there is room in the outbound queue right away, failing otherwise regardless of the
+    /// overflow policy.
+    pub fn try_emit<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
+        self.status.ensure(ConnectionStatus::Ready, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        let message = self.namespace.encode_packet_to_message(
+            &WsIoPacket::new_event(
+                event.as_ref(),
+                data.map(|data| self.packet_codec().encode_data(data)).transpose()?,
+            ),
+            self.packet_codec(),
+        )?;
+
+        if !self.message_queue.try_push(message)? {
+            bail!("Outbound queue is full");
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn wait_for<D: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        event: impl AsRef<str>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Arc<D>>> + Send {
+        self.event_registry.wait_for(event.as_ref(), timeout)
+    }
+
+    #[inline]
+    pub fn wait_for_event<E: WsIoEvent>(&self, timeout: Duration) -> impl Future<Output = Result<Arc<E>>> + Send {
+        self.event_registry.wait_for(E::NAME, timeout)
+    }
 }
 
 // Constants/Statics
@@ -16,6 +16,7 @@ mod runtime;
 use crate::request_adapters::tower::layer::WsIoServerLayer;
 use crate::{
     builder::WsIoServerBuilder,
+    core::traits::event::definition::WsIoEvent,
     namespace::{
         WsIoServerNamespace,
         builder::WsIoServerNamespaceBuilder,
@@ -42,6 +43,10 @@ impl WsIoServer {
         self.0.emit(event.as_ref(), data).await
     }
 
+    pub async fn emit_event<E: WsIoEvent>(&self, event: &E) -> Result<()> {
+        self.0.emit(E::NAME, Some(event)).await
+    }
+
     #[cfg(feature = "tower")]
     pub fn layer(&self) -> WsIoServerLayer {
         WsIoServerLayer::new(self.0.clone())
@@ -3,7 +3,10 @@ use std::{
     time::Duration,
 };
 
-use anyhow::Result;
+use anyhow::{
+    Result,
+    bail,
+};
 use serde::{
     Serialize,
     de::DeserializeOwned,
@@ -16,7 +19,18 @@ use super::{
 };
 use crate::{
     connection::WsIoServerConnection,
-    core::packet::codecs::WsIoPacketCodec,
+    core::{
+        event::{
+            dispatch::{
+                WsIoEventDispatchMode,
+                WsIoEventOverflowPolicy,
+            },
+            interceptor::WsIoEventNext,
+            registry::WsIoEventPayload,
+        },
+        outbound::overflow::WsIoOutboundOverflowPolicy,
+        packet::codecs::WsIoPacketCodec,
+    },
     runtime::WsIoServerRuntime,
 };
 
@@ -31,19 +45,30 @@ impl WsIoServerNamespaceBuilder {
         Self {
             config: WsIoServerNamespaceConfig {
                 broadcast_concurrency_limit: runtime.config.broadcast_concurrency_limit,
+                event_dispatch_mode: WsIoEventDispatchMode::default(),
+                event_handler_timeout: runtime.config.event_handler_timeout,
+                event_interceptors: Vec::new(),
+                event_overflow_policy: runtime.config.event_overflow_policy,
+                heartbeat_interval: runtime.config.heartbeat_interval,
+                heartbeat_timeout: runtime.config.heartbeat_timeout,
                 init_request_handler: None,
                 init_request_handler_timeout: runtime.config.init_request_handler_timeout,
                 init_response_handler: None,
                 init_response_handler_timeout: runtime.config.init_response_handler_timeout,
                 init_response_timeout: runtime.config.init_response_timeout,
+                max_consecutive_event_errors: runtime.config.max_consecutive_event_errors,
+                max_in_flight_events: runtime.config.max_in_flight_events,
                 middleware: None,
                 middleware_execution_timeout: runtime.config.middleware_execution_timeout,
                 on_connect_handler: None,
                 on_close_handler_timeout: runtime.config.on_close_handler_timeout,
                 on_connect_handler_timeout: runtime.config.on_connect_handler_timeout,
+                on_event_error_handler: None,
                 on_ready_handler: None,
-                packet_codec: runtime.config.packet_codec,
+                outbound_overflow_policy: runtime.config.outbound_overflow_policy,
+                packet_codecs: runtime.config.packet_codecs.clone(),
                 path: path.into(),
+                stream_credit_window: runtime.config.stream_credit_window,
                 websocket_config: runtime.config.websocket_config,
             },
             runtime,
@@ -56,6 +81,47 @@ impl WsIoServerNamespaceBuilder {
         self
     }
 
+    /// Stops pinging connections, relying on the transport to notice dead ones.
+    pub fn disable_heartbeat(mut self) -> Self {
+        self.config.heartbeat_interval = None;
+        self
+    }
+
+    pub fn event_dispatch_mode(mut self, event_dispatch_mode: WsIoEventDispatchMode) -> Self {
+        self.config.event_dispatch_mode = event_dispatch_mode;
+        self
+    }
+
+    pub fn event_handler_timeout(mut self, duration: Duration) -> Self {
+        self.config.event_handler_timeout = Some(duration);
+        self
+    }
+
+    pub fn event_overflow_policy(mut self, event_overflow_policy: WsIoEventOverflowPolicy) -> Self {
+        self.config.event_overflow_policy = event_overflow_policy;
+        self
+    }
+
+    pub fn heartbeat_interval(mut self, duration: Duration) -> Self {
+        self.config.heartbeat_interval = Some(duration);
+        self
+    }
+
+    pub fn heartbeat_timeout(mut self, duration: Duration) -> Self {
+        self.config.heartbeat_timeout = duration;
+        self
+    }
+
+    pub fn max_consecutive_event_errors(mut self, max_consecutive_event_errors: u32) -> Self {
+        self.config.max_consecutive_event_errors = Some(max_consecutive_event_errors);
+        self
+    }
+
+    pub fn max_in_flight_events(mut self, max_in_flight_events: usize) -> Self {
+        self.config.max_in_flight_events = Some(max_in_flight_events);
+        self
+    }
+
     pub fn middleware_execution_timeout(mut self, duration: Duration) -> Self {
         self.config.middleware_execution_timeout = duration;
         self
@@ -95,6 +161,18 @@ impl WsIoServerNamespaceBuilder {
         self
     }
 
+    pub fn on_event_error<H, Fut>(mut self, handler: H) -> Self
+    where
+        H: Fn(Arc<WsIoServerConnection>, String, anyhow::Error) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.config.on_event_error_handler = Some(Box::new(move |connection, event, error| {
+            Box::pin(handler(connection, event, error))
+        }));
+
+        self
+    }
+
     pub fn on_ready<H, Fut>(mut self, handler: H) -> Self
     where
         H: Fn(Arc<WsIoServerConnection>) -> Fut + Send + Sync + 'static,
@@ -104,17 +182,50 @@ impl WsIoServerNamespaceBuilder {
         self
     }
 
-    pub fn packet_codec(mut self, packet_codec: WsIoPacketCodec) -> Self {
-        self.config.packet_codec = packet_codec;
+    pub fn outbound_overflow_policy(mut self, policy: WsIoOutboundOverflowPolicy) -> Self {
+        self.config.outbound_overflow_policy = policy;
+        self
+    }
+
+    pub fn packet_codec(mut self, packet_codec: impl Into<WsIoPacketCodec>) -> Self {
+        self.config.packet_codecs = vec![packet_codec.into()];
+        self
+    }
+
+    pub fn packet_codecs<I: IntoIterator<Item = C>, C: Into<WsIoPacketCodec>>(mut self, packet_codecs: I) -> Self {
+        self.config.packet_codecs = packet_codecs.into_iter().map(Into::into).collect();
         self
     }
 
     pub fn register(self) -> Result<Arc<WsIoServerNamespace>> {
+        if self.config.packet_codecs.is_empty() {
+            bail!("Namespace {} must accept at least one packet codec", self.config.path);
+        }
+
+        // Codecs are negotiated by name, so a duplicate one could never be selected
+        for (index, packet_codec) in self.config.packet_codecs.iter().enumerate() {
+            if self.config.packet_codecs[..index]
+                .iter()
+                .any(|accepted_packet_codec| accepted_packet_codec.name() == packet_codec.name())
+            {
+                bail!(
+                    "Namespace {} accepts more than one packet codec named '{}'",
+                    self.config.path,
+                    packet_codec.name()
+                );
+            }
+        }
+
         let namespace = WsIoServerNamespace::new(self.config, self.runtime.clone());
         self.runtime.insert_namespace(namespace.clone())?;
         Ok(namespace)
     }
 
+    pub fn stream_credit_window(mut self, stream_credit_window: u32) -> Self {
+        self.config.stream_credit_window = stream_credit_window;
+        self
+    }
+
     pub fn websocket_config(mut self, websocket_config: WebSocketConfig) -> Self {
         self.config.websocket_config = websocket_config;
         self
@@ -125,6 +236,20 @@ impl WsIoServerNamespaceBuilder {
         self
     }
 
+    pub fn with_event_interceptor<H, Fut>(mut self, interceptor: H) -> Self
+    where
+        H: Fn(Arc<WsIoServerConnection>, String, WsIoEventPayload, WsIoEventNext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.config
+            .event_interceptors
+            .push(Arc::new(move |connection, event, payload, next| {
+                Box::pin(interceptor(connection, event, payload, next))
+            }));
+
+        self
+    }
+
     pub fn with_middleware<H, Fut>(mut self, handler: H) -> Self
     where
         H: Fn(Arc<WsIoServerConnection>) -> Fut + Send + Sync + 'static,
@@ -10,6 +10,14 @@ use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use crate::{
     connection::WsIoServerConnection,
     core::{
+        event::{
+            dispatch::{
+                WsIoEventDispatchMode,
+                WsIoEventOverflowPolicy,
+            },
+            interceptor::WsIoEventInterceptor,
+        },
+        outbound::overflow::WsIoOutboundOverflowPolicy,
         packet::codecs::WsIoPacketCodec,
         types::{
             ArcAsyncUnaryResultHandler,
@@ -19,6 +27,17 @@ use crate::{
 };
 
 // Types
+type EventErrorHandler = Box<
+    dyn Fn(
+            Arc<WsIoServerConnection>,
+            String,
+            anyhow::Error,
+        ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
 type InitRequestHandler = Box<
     dyn for<'a> Fn(
             Arc<WsIoServerConnection>,
@@ -45,6 +64,26 @@ pub(crate) struct WsIoServerNamespaceConfig {
     /// Maximum number of concurrent broadcast operations.
     pub(crate) broadcast_concurrency_limit: usize,
 
+    /// Order in which the events received from each connection are handled.
+    pub(crate) event_dispatch_mode: WsIoEventDispatchMode,
+
+    /// Interceptors wrapping the handlers of every event received, the first one added being the outermost.
+    ///
+    /// They run in the job of the event, so they follow the dispatch mode and count towards the in-flight limit.
+    pub(crate) event_interceptors: Vec<WsIoEventInterceptor<WsIoServerConnection>>,
+
+    /// Maximum duration allowed for an event handler to execute, unless overridden when it is registered.
+    pub(crate) event_handler_timeout: Option<Duration>,
+
+    /// Action taken when a connection sends an event while `max_in_flight_events` of its events are being handled.
+    pub(crate) event_overflow_policy: WsIoEventOverflowPolicy,
+
+    /// Interval between ping packets sent to each connection, `None` disabling the heartbeat.
+    pub(crate) heartbeat_interval: Option<Duration>,
+
+    /// Maximum duration to wait for any packet from the client after a ping before closing the connection.
+    pub(crate) heartbeat_timeout: Duration,
+
     pub(crate) init_request_handler: Option<InitRequestHandler>,
 
     /// Maximum duration allowed for the init request handler to execute.
@@ -58,6 +97,12 @@ pub(crate) struct WsIoServerNamespaceConfig {
     /// Maximum duration to wait for the client to send the init response packet.
     pub(crate) init_response_timeout: Duration,
 
+    /// Number of consecutive event handler or payload decode failures after which a connection is disconnected.
+    pub(crate) max_consecutive_event_errors: Option<u32>,
+
+    /// Maximum number of events per connection whose handlers may be running at the same time.
+    pub(crate) max_in_flight_events: Option<usize>,
+
     pub(crate) middleware: Option<BoxAsyncUnaryResultHandler<WsIoServerConnection>>,
 
     /// Maximum duration allowed for middleware execution.
@@ -71,11 +116,23 @@ pub(crate) struct WsIoServerNamespaceConfig {
 
     pub(crate) on_connect_handler: Option<BoxAsyncUnaryResultHandler<WsIoServerConnection>>,
 
+    /// Invoked when the payload of an event fails to decode or one of its handlers fails.
+    pub(crate) on_event_error_handler: Option<EventErrorHandler>,
+
     pub(crate) on_ready_handler: Option<ArcAsyncUnaryResultHandler<WsIoServerConnection>>,
 
-    pub(crate) packet_codec: WsIoPacketCodec,
+    /// Action taken when a message is sent to a connection whose outbound queue is full.
+    pub(crate) outbound_overflow_policy: WsIoOutboundOverflowPolicy,
+
+    /// Packet codecs accepted during the handshake, in order of preference.
+    ///
+    /// The first one is used for clients that do not advertise a codec.
+    pub(crate) packet_codecs: Vec<WsIoPacketCodec>,
 
     pub(super) path: String,
 
+    /// Number of chunks a connection may send on each stream it opens before waiting for more credit.
+    pub(crate) stream_credit_window: u32,
+
     pub(crate) websocket_config: WebSocketConfig,
 }
@@ -47,7 +47,14 @@ use crate::{
     connection::WsIoServerConnection,
     core::{
         atomic::status::AtomicStatus,
-        packet::WsIoPacket,
+        packet::{
+            WsIoPacket,
+            codecs::WsIoPacketCodec,
+        },
+        traits::{
+            error::sender::ErrorSender,
+            event::definition::WsIoEvent,
+        },
         types::hashers::{
             FxDashMap,
             FxDashSet,
@@ -94,6 +101,7 @@ impl WsIoServerNamespace {
     async fn handle_upgraded_request(
         self: &Arc<Self>,
         headers: HeaderMap,
+        packet_codec_index: usize,
         request_uri: Uri,
         upgraded: Upgraded,
     ) -> Result<()> {
@@ -105,7 +113,13 @@ impl WsIoServerNamespace {
         // Check runtime and namespace status
         if !self.runtime.status.is(WsIoServerRuntimeStatus::Running) || !self.status.is(NamespaceStatus::Running) {
             ws_stream
-                .send((*self.encode_packet_to_message(&WsIoPacket::new_disconnect())?).clone())
+                .send(
+                    (*self.encode_packet_to_message(
+                        &WsIoPacket::new_disconnect(),
+                        &self.config.packet_codecs[packet_codec_index],
+                    )?)
+                    .clone(),
+                )
                 .await?;
 
             let _ = ws_stream.close(None).await;
@@ -113,7 +127,8 @@ impl WsIoServerNamespace {
         }
 
         // Create connection
-        let (connection, mut message_rx) = WsIoServerConnection::new(headers, self.clone(), request_uri);
+        let (connection, mut message_rx) =
+            WsIoServerConnection::new(headers, self.clone(), packet_codec_index, request_uri);
 
         // Split ws stream and spawn read and write tasks
         let (mut ws_stream_writer, mut ws_stream_reader) = ws_stream.split();
@@ -121,9 +136,9 @@ impl WsIoServerNamespace {
         let mut read_ws_stream_task = spawn(async move {
             while let Some(message) = ws_stream_reader.next().await {
                 if match message {
-                    Ok(Message::Binary(bytes)) => connection_clone.handle_incoming_packet(&bytes).await,
+                    Ok(Message::Binary(bytes)) => connection_clone.handle_incoming_packet(bytes, true).await,
                     Ok(Message::Close(_)) => break,
-                    Ok(Message::Text(text)) => connection_clone.handle_incoming_packet(text.as_bytes()).await,
+                    Ok(Message::Text(text)) => connection_clone.handle_incoming_packet(text.into(), false).await,
                     Err(_) => break,
                     _ => Ok(()),
                 }
@@ -136,7 +151,7 @@ impl WsIoServerNamespace {
 
         let mut write_ws_stream_task = spawn(async move {
             while let Some(message) = message_rx.recv().await {
-                let message = (*message).clone();
+                let message = (**message.message()).clone();
                 let is_close = matches!(message, Message::Close(_));
                 if ws_stream_writer.send(message).await.is_err() {
                     break;
@@ -152,7 +167,7 @@ impl WsIoServerNamespace {
         // Try to init connection
         match connection.init().await {
             Ok(_) => {
-                // Wait for either read or write task to finish
+                // Wait for either read or write task to finish, or for the heartbeat to time out
                 select! {
                     _ = &mut read_ws_stream_task => {
                         write_ws_stream_task.abort();
@@ -160,10 +175,16 @@ impl WsIoServerNamespace {
                     _ = &mut write_ws_stream_task => {
                         read_ws_stream_task.abort();
                     },
+                    _ = connection.run_heartbeat() => {
+                        // Client is unresponsive, drop the socket without waiting for a graceful close
+                        read_ws_stream_task.abort();
+                        write_ws_stream_task.abort();
+                    },
                 }
             }
-            Err(_) => {
-                // Close connection
+            Err(err) => {
+                // Report the failure to the client and close connection
+                let _ = connection.send_error(&err, None).await;
                 read_ws_stream_task.abort();
                 connection.close();
                 let _ = join!(read_ws_stream_task, write_ws_stream_task);
@@ -186,24 +207,52 @@ impl WsIoServerNamespace {
     }
 
     #[inline]
-    pub(crate) fn encode_packet_to_message(&self, packet: &WsIoPacket) -> Result<Arc<Message>> {
-        let bytes = self.config.packet_codec.encode(packet)?;
-        Ok(Arc::new(match self.config.packet_codec.is_text() {
-            true => Message::Text(unsafe { String::from_utf8_unchecked(bytes).into() }),
+    pub(crate) fn encode_packet_to_message(
+        &self,
+        packet: &WsIoPacket,
+        packet_codec: &WsIoPacketCodec,
+    ) -> Result<Arc<Message>> {
+        let bytes = packet_codec.encode(packet)?;
+        Ok(Arc::new(match packet_codec.is_text_frame(packet) {
+            true => Message::Text(packet_codec.into_text(bytes)?.into()),
             false => Message::Binary(bytes.into()),
         }))
     }
 
+    /// Picks the first sub-protocol advertised by the client that matches an accepted packet codec.
+    ///
+    /// Returns the default codec when nothing is advertised, or `None` when no advertised codec is accepted.
+    pub(crate) fn negotiate_packet_codec<'a, I: IntoIterator<Item = &'a str>>(
+        &self,
+        sub_protocols: I,
+    ) -> Option<(usize, Option<&'a str>)> {
+        let mut sub_protocols = sub_protocols.into_iter().peekable();
+        if sub_protocols.peek().is_none() {
+            return Some((0, None));
+        }
+
+        sub_protocols.find_map(|sub_protocol| {
+            self.config
+                .packet_codecs
+                .iter()
+                .position(|packet_codec| packet_codec.sub_protocol() == sub_protocol)
+                .map(|index| (index, Some(sub_protocol)))
+        })
+    }
+
     pub(crate) async fn handle_on_upgrade_request(
         self: &Arc<Self>,
         headers: HeaderMap,
         on_upgrade: OnUpgrade,
+        packet_codec_index: usize,
         request_uri: Uri,
     ) {
         let namespace = self.clone();
         self.connection_task_set.lock().await.spawn(async move {
             if let Ok(upgraded) = on_upgrade.await {
-                let _ = namespace.handle_upgraded_request(headers, request_uri, upgraded).await;
+                let _ = namespace
+                    .handle_upgraded_request(headers, packet_codec_index, request_uri, upgraded)
+                    .await;
             }
         });
     }
@@ -242,6 +291,10 @@ impl WsIoServerNamespace {
             .await
     }
 
+    pub async fn emit_event<E: WsIoEvent>(self: &Arc<Self>, event: &E) -> Result<()> {
+        self.emit(E::NAME, Some(event)).await
+    }
+
     #[inline]
     pub fn except<I: IntoIterator<Item = S>, S: AsRef<str>>(
         self: &Arc<Self>,
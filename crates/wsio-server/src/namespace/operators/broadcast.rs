@@ -3,13 +3,17 @@ use std::{
     sync::Arc,
 };
 
-use anyhow::Result;
+use anyhow::{
+    Result,
+    bail,
+};
 use futures_util::{
     StreamExt,
     future::ready,
     stream::iter,
 };
 use serde::Serialize;
+use tokio_tungstenite::tungstenite::Message;
 
 use super::super::{
     NamespaceStatus,
@@ -18,7 +22,15 @@ use super::super::{
 use crate::{
     connection::WsIoServerConnection,
     core::{
-        packet::WsIoPacket,
+        outbound::{
+            priority::WsIoOutboundPriority,
+            queue::WsIoOutboundMessage,
+        },
+        packet::{
+            WsIoPacket,
+            codecs::WsIoPacketCodec,
+        },
+        traits::event::definition::WsIoEvent,
         types::hashers::FxHashSet,
     },
 };
@@ -43,11 +55,64 @@ impl WsIoServerNamespaceBroadcastOperator {
     }
 
     // Private methods
-    async fn for_each_target_connections<F, Fut>(&self, f: F)
+    /// Sends the packet built by `build_packet` to every target connection through `send_message`.
+    ///
+    /// The packet is built and encoded once per packet codec negotiated by at least one target, since connections may
+    /// have negotiated different ones.
+    async fn broadcast_packet<'a, B, S, Fut>(&self, build_packet: B, send_message: S) -> Result<()>
+    where
+        B: Fn(&WsIoPacketCodec) -> Result<WsIoPacket<'a>>,
+        S: Fn(Arc<WsIoServerConnection>, Arc<Message>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let packet_codecs = &self.namespace.config.packet_codecs;
+        let mut messages = vec![None; packet_codecs.len()];
+        let mut targets = Vec::new();
+        for connection in self.target_connections() {
+            let packet_codec_index = connection.packet_codec_index();
+            if messages[packet_codec_index].is_none() {
+                let packet_codec = &packet_codecs[packet_codec_index];
+                messages[packet_codec_index] = Some(
+                    self.namespace
+                        .encode_packet_to_message(&build_packet(packet_codec)?, packet_codec)?,
+                );
+            }
+
+            if let Some(message) = &messages[packet_codec_index] {
+                targets.push((connection, message.clone()));
+            }
+        }
+
+        iter(targets)
+            .for_each_concurrent(
+                self.namespace.config.broadcast_concurrency_limit,
+                |(connection, message)| {
+                    let send = send_message(connection, message);
+                    async move {
+                        let _ = send.await;
+                    }
+                },
+            )
+            .await;
+
+        Ok(())
+    }
+
+    /// Emits the packet built by `build_packet` to every target connection, see [`Self::broadcast_packet`].
+    async fn emit_packet<'a, B, S, Fut>(&self, build_packet: B, send_message: S) -> Result<()>
     where
-        F: Fn(Arc<WsIoServerConnection>) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<()>> + Send + 'static,
+        B: Fn(&WsIoPacketCodec) -> Result<WsIoPacket<'a>>,
+        S: Fn(Arc<WsIoServerConnection>, Arc<Message>) -> Fut,
+        Fut: Future<Output = Result<()>>,
     {
+        self.namespace.status.ensure(NamespaceStatus::Running, |status| {
+            format!("Cannot emit in invalid status: {status:?}")
+        })?;
+
+        self.broadcast_packet(build_packet, send_message).await
+    }
+
+    fn target_connections(&self) -> Vec<Arc<WsIoServerConnection>> {
         let mut target_connection_ids = FxHashSet::default();
         if self.include_rooms.is_empty() {
             target_connection_ids.extend(self.namespace.connections.iter().map(|entry| *entry.key()));
@@ -68,54 +133,144 @@ impl WsIoServerNamespaceBroadcastOperator {
         }
 
         for exclude_connection_id in &self.exclude_connection_ids {
-            target_connection_ids.remove(&exclude_connection_id);
+            target_connection_ids.remove(exclude_connection_id);
         }
 
-        iter(target_connection_ids)
+        target_connection_ids
+            .into_iter()
             .filter_map(|target_connection_id| {
-                ready(
-                    self.namespace
-                        .connections
-                        .get(&target_connection_id)
-                        .map(|entry| entry.value().clone()),
-                )
-            })
-            .for_each_concurrent(self.namespace.config.broadcast_concurrency_limit, |connection| async {
-                let _ = f(connection).await;
+                self.namespace
+                    .connections
+                    .get(&target_connection_id)
+                    .map(|entry| entry.value().clone())
             })
-            .await;
+            .collect()
     }
 
     // Public methods
     pub async fn disconnect(&self) -> Result<()> {
-        let message = self.namespace.encode_packet_to_message(&WsIoPacket::new_disconnect())?;
-        self.for_each_target_connections(move |connection| {
-            let message = message.clone();
-            async move { connection.send_message(message).await }
-        })
-        .await;
-
-        Ok(())
+        self.broadcast_packet(
+            |_| Ok(WsIoPacket::new_disconnect()),
+            |connection, message| async move { connection.send_message(message).await },
+        )
+        .await
     }
 
     pub async fn emit<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
-        self.namespace.status.ensure(NamespaceStatus::Running, |status| {
-            format!("Cannot emit in invalid status: {status:?}")
-        })?;
+        self.emit_packet(
+            |packet_codec| {
+                Ok(WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| packet_codec.encode_data(data)).transpose()?,
+                ))
+            },
+            |connection, message| async move { connection.emit_event_message(message).await },
+        )
+        .await
+    }
+
+    /// Emits `event`, replacing the message queued for each target connection with the same `coalescing_key` if it
+    /// has not been sent yet.
+    pub async fn emit_coalesced<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        coalescing_key: impl AsRef<str>,
+    ) -> Result<()> {
+        let coalescing_key: Arc<str> = coalescing_key.as_ref().into();
+        self.emit_packet(
+            |packet_codec| {
+                Ok(WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| packet_codec.encode_data(data)).transpose()?,
+                ))
+            },
+            |connection, message| {
+                let message = WsIoOutboundMessage::coalesced(message, coalescing_key.clone());
+                async move { connection.emit_event_message(message).await }
+            },
+        )
+        .await
+    }
 
-        let message = self.namespace.encode_packet_to_message(&WsIoPacket::new_event(
-            event.as_ref(),
-            data.map(|data| self.namespace.config.packet_codec.encode_data(data))
-                .transpose()?,
-        ))?;
+    pub async fn emit_event<E: WsIoEvent>(&self, event: &E) -> Result<()> {
+        self.emit(E::NAME, Some(event)).await
+    }
 
-        self.for_each_target_connections(move |connection| {
-            let message = message.clone();
-            async move { connection.emit_event_message(message).await }
-        })
-        .await;
+    /// Emits `event` with a payload that is already encoded, sent unchanged to every target connection.
+    ///
+    /// Fails if the namespace accepts more than one packet codec, since the payload can only be readable by one of
+    /// them.
+    pub async fn emit_raw(&self, event: impl AsRef<str>, data: Option<&[u8]>) -> Result<()> {
+        if self.namespace.config.packet_codecs.len() > 1 {
+            bail!("Cannot broadcast raw payloads in a namespace accepting more than one packet codec");
+        }
 
-        Ok(())
+        self.emit_packet(
+            |_| Ok(WsIoPacket::new_raw_event(event.as_ref(), data)),
+            |connection, message| async move { connection.emit_event_message(message).await },
+        )
+        .await
+    }
+
+    /// Emits `event` without waiting on slow connections, dropping it for each target that is not ready or whose
+    /// outgoing queue is full.
+    pub async fn emit_volatile<D: Serialize>(&self, event: impl AsRef<str>, data: Option<&D>) -> Result<()> {
+        self.emit_packet(
+            |packet_codec| {
+                Ok(WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| packet_codec.encode_data(data)).transpose()?,
+                ))
+            },
+            |connection, message| {
+                connection.emit_volatile_event_message(message);
+                ready(Ok(()))
+            },
+        )
+        .await
+    }
+
+    /// Emits `event` with `attachments` sent as binary blobs next to its payload, without going through the codec.
+    pub async fn emit_with_attachments<D: Serialize, B: AsRef<[u8]>>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        attachments: &[B],
+    ) -> Result<()> {
+        self.emit_packet(
+            |packet_codec| {
+                Ok(WsIoPacket::new_event_with_attachments(
+                    event.as_ref(),
+                    data.map(|data| packet_codec.encode_data(data)).transpose()?,
+                    attachments.iter().map(AsRef::as_ref).collect(),
+                ))
+            },
+            |connection, message| async move { connection.emit_event_message(message).await },
+        )
+        .await
+    }
+
+    /// Emits `event` through the outbound lane of `priority` of each target connection.
+    pub async fn emit_with_priority<D: Serialize>(
+        &self,
+        event: impl AsRef<str>,
+        data: Option<&D>,
+        priority: WsIoOutboundPriority,
+    ) -> Result<()> {
+        self.emit_packet(
+            |packet_codec| {
+                Ok(WsIoPacket::new_event(
+                    event.as_ref(),
+                    data.map(|data| packet_codec.encode_data(data)).transpose()?,
+                ))
+            },
+            |connection, message| {
+                let message = WsIoOutboundMessage::from(message).with_priority(priority);
+                async move { connection.emit_event_message(message).await }
+            },
+        )
+        .await
     }
 
     #[inline]
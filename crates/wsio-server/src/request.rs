@@ -10,6 +10,7 @@ use http::{
         CONNECTION,
         SEC_WEBSOCKET_ACCEPT,
         SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL,
         SEC_WEBSOCKET_VERSION,
         UPGRADE,
     },
@@ -65,6 +66,22 @@ pub(super) async fn dispatch_request<ReqBody, ResBody: Default, E: Send>(
         return respond(StatusCode::NOT_FOUND);
     };
 
+    // Negotiate packet codec from advertised sub-protocols
+    let Some((packet_codec_index, sub_protocol)) = namespace.negotiate_packet_codec(
+        request
+            .headers()
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|sub_protocol| !sub_protocol.is_empty()),
+    ) else {
+        return respond(StatusCode::NOT_ACCEPTABLE);
+    };
+
+    let sub_protocol = sub_protocol.map(String::from);
+
     // Generate accept key
     let ws_accept_key = derive_accept_key(ws_sec_key.as_bytes());
 
@@ -75,16 +92,25 @@ pub(super) async fn dispatch_request<ReqBody, ResBody: Default, E: Send>(
     };
 
     namespace
-        .handle_on_upgrade_request(request.headers().clone(), on_upgrade, request.uri().clone())
+        .handle_on_upgrade_request(
+            request.headers().clone(),
+            on_upgrade,
+            packet_codec_index,
+            request.uri().clone(),
+        )
         .await;
 
-    Ok(Response::builder()
+    let mut response_builder = Response::builder()
         .status(StatusCode::SWITCHING_PROTOCOLS)
         .header(CONNECTION, "Upgrade")
         .header(SEC_WEBSOCKET_ACCEPT, ws_accept_key)
-        .header(UPGRADE, "websocket")
-        .body(ResBody::default())
-        .unwrap())
+        .header(UPGRADE, "websocket");
+
+    if let Some(sub_protocol) = sub_protocol {
+        response_builder = response_builder.header(SEC_WEBSOCKET_PROTOCOL, sub_protocol);
+    }
+
+    Ok(response_builder.body(ResBody::default()).unwrap())
 }
 
 #[inline]
@@ -25,7 +25,7 @@ static BINCODE: LazyLock<WsIoClient> = LazyLock::new(|| {
         .packet_codec(WsIoPacketCodec::Bincode)
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -38,7 +38,7 @@ static CBOR: LazyLock<WsIoClient> = LazyLock::new(|| {
         .packet_codec(WsIoPacketCodec::Cbor)
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -50,7 +50,7 @@ static DISCONNECT: LazyLock<WsIoClient> = LazyLock::new(|| {
         .on_session_ready(|session| on_session_ready(session, NAMESPACE))
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -63,7 +63,7 @@ static MSG_PACK: LazyLock<WsIoClient> = LazyLock::new(|| {
         .packet_codec(WsIoPacketCodec::MsgPack)
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -76,7 +76,7 @@ static INIT: LazyLock<WsIoClient> = LazyLock::new(|| {
         .with_init_handler(|_, _: Option<()>| async { Ok(Some(())) })
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -89,7 +89,7 @@ static POSTCARD: LazyLock<WsIoClient> = LazyLock::new(|| {
         .packet_codec(WsIoPacketCodec::Postcard)
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -102,7 +102,7 @@ static SERDE_JSON: LazyLock<WsIoClient> = LazyLock::new(|| {
         .packet_codec(WsIoPacketCodec::SerdeJson)
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 
@@ -115,7 +115,7 @@ static SONIC_RS: LazyLock<WsIoClient> = LazyLock::new(|| {
         .packet_codec(WsIoPacketCodec::SonicRs)
         .build();
 
-    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE));
+    client.on("test", |_, _: Arc<()>| on_event(NAMESPACE)).detach();
     client
 });
 